@@ -0,0 +1,71 @@
+use codecrafters_interpreter::interpreter::{Interpreter, TreeWalk};
+use codecrafters_interpreter::parser::{Parser, RecursiveDecendantParser};
+use codecrafters_interpreter::resolver::Resolver;
+use codecrafters_interpreter::scanner::Scanner;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const ARITHMETIC_LOOP: &str = r#"
+var sum = 0;
+for (var i = 0; i < 10000; i = i + 1) {
+    sum = sum + i * 2 - i / 2;
+}
+"#;
+
+const RECURSIVE_FIB: &str = r#"
+fun fib(n) {
+    if (n < 2) return n;
+    return fib(n - 1) + fib(n - 2);
+}
+var result = fib(18);
+"#;
+
+const METHOD_DISPATCH: &str = r#"
+class Counter {
+    init() {
+        this.count = 0;
+    }
+
+    increment() {
+        this.count = this.count + 1;
+        return this;
+    }
+}
+
+var counter = Counter();
+for (var i = 0; i < 5000; i = i + 1) {
+    counter.increment().increment();
+}
+"#;
+
+const STRING_BUILDING: &str = r#"
+var text = "";
+for (var i = 0; i < 2000; i = i + 1) {
+    text = text + "ab";
+}
+var repeated = "xyz" * 2000;
+"#;
+
+/// Runs a script through the whole pipeline: scanning, parsing, resolution and evaluation.
+fn run(source: &str) {
+    let scanner = Scanner::new(source.as_bytes().to_vec());
+    let parser = RecursiveDecendantParser::new();
+    let mut statements = parser.parse(&scanner).expect("benchmark script should parse");
+    let mut resolver = Resolver::new();
+    statements.iter_mut().for_each(|stmt| resolver.resolve_stmt(stmt));
+    assert!(!resolver.has_err(), "benchmark script should resolve");
+
+    let mut interpreter = TreeWalk::new();
+    for stmt in &statements {
+        interpreter.interpret(stmt).expect("benchmark script should run");
+    }
+}
+
+fn scripts(c: &mut Criterion) {
+    c.bench_function("arithmetic loop", |b| b.iter(|| run(black_box(ARITHMETIC_LOOP))));
+    c.bench_function("recursive fib", |b| b.iter(|| run(black_box(RECURSIVE_FIB))));
+    c.bench_function("method dispatch", |b| b.iter(|| run(black_box(METHOD_DISPATCH))));
+    c.bench_function("string building", |b| b.iter(|| run(black_box(STRING_BUILDING))));
+}
+
+criterion_group!(benches, scripts);
+criterion_main!(benches);
@@ -0,0 +1,481 @@
+use crate::syntax::*;
+use crate::token::Token;
+
+const INDENT: &str = "    ";
+
+/// Renders parsed statements back into Lox source with consistent indentation and spacing.
+///
+/// Comments are not part of the syntax tree and `for` loops are desugared by the parser, so both come
+/// back out in their parsed form.
+pub fn format_program(statements: &[Statement]) -> String {
+    let mut printer = SourcePrinter::default();
+    printer.statements(statements);
+    printer.out
+}
+
+#[derive(Default)]
+struct SourcePrinter {
+    out: String,
+    depth: usize,
+}
+
+impl SourcePrinter {
+    fn statements(&mut self, statements: &[Statement]) {
+        for (i, stmt) in statements.iter().enumerate() {
+            if i > 0 && (is_declaration_block(stmt) || is_declaration_block(&statements[i - 1])) {
+                self.out.push('\n');
+            }
+            self.line_start();
+            self.statement(stmt);
+            self.out.push('\n');
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::FunDecl(decl) => {
+                self.out.push_str("fun ");
+                self.function(decl);
+            },
+            Statement::VarDecl(VariableDecl { name, initializer }) => {
+                self.out.push_str("var ");
+                self.out.push_str(name.lexeme);
+                if let Some(initializer) = initializer {
+                    self.out.push_str(" = ");
+                    self.expr(initializer);
+                }
+                self.out.push(';');
+            },
+            Statement::DestructureDecl(DestructureDecl { names, initializer, .. }) => {
+                let names: Vec<&str> = names.iter().map(|name| name.lexeme).collect();
+                self.out.push_str(&format!("var [{}] = ", names.join(", ")));
+                self.expr(initializer);
+                self.out.push(';');
+            },
+            Statement::ClassDecl(ClassDecl { name, superclass, methods }) => {
+                self.out.push_str("class ");
+                self.out.push_str(name.lexeme);
+                if let Some(superclass) = superclass {
+                    self.out.push_str(" < ");
+                    self.expr(superclass);
+                }
+                self.out.push_str(" {\n");
+                self.depth += 1;
+                for (i, method) in methods.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push('\n');
+                    }
+                    self.line_start();
+                    self.function(method);
+                    self.out.push('\n');
+                }
+                self.depth -= 1;
+                self.line_start();
+                self.out.push('}');
+            },
+            Statement::Print(PrintStatement { exprs, .. }) => {
+                self.out.push_str("print ");
+                self.comma_separated(exprs);
+                self.out.push(';');
+            },
+            Statement::Expr(ExpressionStatement { expr }) => {
+                self.expr(expr);
+                if !expr.ends_with_lambda() {
+                    self.out.push(';');
+                }
+            },
+            Statement::Block(block) => self.block(&block.statements),
+            Statement::If(IfStatemnet {
+                condition,
+                if_branch,
+                else_branch,
+            }) => {
+                self.out.push_str("if (");
+                self.expr(condition);
+                self.out.push(')');
+                self.body(if_branch);
+                if let Some(else_branch) = else_branch {
+                    match if_branch.as_ref() {
+                        Statement::Block(_) => self.out.push(' '),
+                        _ => {
+                            self.out.push('\n');
+                            self.line_start();
+                        },
+                    }
+                    self.out.push_str("else");
+                    match else_branch.as_ref() {
+                        Statement::If(_) => {
+                            self.out.push(' ');
+                            self.statement(else_branch);
+                        },
+                        _ => self.body(else_branch),
+                    }
+                }
+            },
+            // A desugared `for` keeps its increment apart from the body, so it's printed back as a `for`
+            Statement::While(WhileStatement {
+                label,
+                condition,
+                body,
+                increment: Some(increment),
+            }) => {
+                self.label(label);
+                self.out.push_str("for (; ");
+                self.expr(condition);
+                self.out.push_str("; ");
+                self.expr(increment);
+                self.out.push(')');
+                self.body(body);
+            },
+            Statement::While(WhileStatement { label, condition, body, .. }) => {
+                self.label(label);
+                self.out.push_str("while (");
+                self.expr(condition);
+                self.out.push(')');
+                self.body(body);
+            },
+            Statement::DoWhile(WhileStatement { label, condition, body, .. }) => {
+                self.label(label);
+                self.out.push_str("do");
+                self.body(body);
+                match body.as_ref() {
+                    Statement::Block(_) => self.out.push(' '),
+                    _ => {
+                        self.out.push('\n');
+                        self.line_start();
+                    },
+                }
+                self.out.push_str("while (");
+                self.expr(condition);
+                self.out.push_str(");");
+            },
+            Statement::Return(ReturnStatement {
+                value: Some(value),
+                implicit: true,
+                ..
+            }) => {
+                self.expr(value);
+                self.out.push(';');
+            },
+            Statement::Return(ReturnStatement { value, .. }) => {
+                self.out.push_str("return");
+                if let Some(value) = value {
+                    self.out.push(' ');
+                    self.expr(value);
+                }
+                self.out.push(';');
+            },
+            Statement::Break(LoopJump { keyword, label }) | Statement::Continue(LoopJump { keyword, label }) => {
+                self.out.push_str(keyword.lexeme);
+                if let Some(label) = label {
+                    self.out.push_str(&format!(" {}", label.lexeme));
+                }
+                self.out.push(';');
+            },
+            Statement::Try(TryStatement {
+                try_block,
+                catch,
+                finally_block,
+            }) => {
+                self.out.push_str("try ");
+                self.block(&try_block.statements);
+                if let Some(CatchClause { name, block }) = catch {
+                    self.out.push_str(&format!(" catch ({}) ", name.lexeme));
+                    self.block(&block.statements);
+                }
+                if let Some(block) = finally_block {
+                    self.out.push_str(" finally ");
+                    self.block(&block.statements);
+                }
+            },
+            Statement::Using(UsingStatement { name, initializer, body, .. }) => {
+                self.out.push_str(&format!("using ({} = ", name.lexeme));
+                self.expr(initializer);
+                self.out.push_str(") ");
+                self.block(&body.statements);
+            },
+            Statement::Throw(ThrowStatement { value, .. }) => {
+                self.out.push_str("throw ");
+                self.expr(value);
+                self.out.push(';');
+            },
+            Statement::Import(ImportStatement { path, alias, .. }) => {
+                self.out.push_str(&format!("import {}", path.lexeme));
+                if let Some(alias) = alias {
+                    self.out.push_str(&format!(" as {}", alias.lexeme));
+                }
+                self.out.push(';');
+            },
+            Statement::Switch(SwitchStatement { subject, cases, default, .. }) => {
+                self.out.push_str("switch (");
+                self.expr(subject);
+                self.out.push_str(") {\n");
+                self.depth += 1;
+                for SwitchCase { patterns, body } in cases {
+                    self.line_start();
+                    self.out.push_str("case ");
+                    for (i, pattern) in patterns.iter().enumerate() {
+                        if i > 0 {
+                            self.out.push_str(", ");
+                        }
+                        match pattern {
+                            CasePattern::Value(value) => self.expr(value),
+                            CasePattern::Guard { operator, value } => {
+                                self.out.push_str(&format!("{} ", operator.lexeme));
+                                self.expr(value);
+                            },
+                        }
+                    }
+                    self.case_body(body);
+                }
+                if let Some(body) = default {
+                    self.line_start();
+                    self.out.push_str("default");
+                    self.case_body(body);
+                }
+                self.depth -= 1;
+                self.line_start();
+                self.out.push('}');
+            },
+        }
+    }
+
+    fn label(&mut self, label: &Option<Token>) {
+        if let Some(label) = label {
+            self.out.push_str(&format!("{}: ", label.lexeme));
+        }
+    }
+
+    fn case_body(&mut self, statements: &[Statement]) {
+        self.out.push_str(":\n");
+        self.depth += 1;
+        self.statements(statements);
+        self.depth -= 1;
+    }
+
+    fn function(&mut self, FunctionDecl { name, params, body, is_getter }: &FunctionDecl) {
+        let params: Vec<&str> = params.iter().map(|param| param.lexeme).collect();
+        match is_getter {
+            true => self.out.push_str(&format!("{} ", name.lexeme)),
+            false => self.out.push_str(&format!("{}({}) ", name.lexeme, params.join(", "))),
+        }
+        match body {
+            FunctionBody::Block(statements) => self.block(statements),
+            FunctionBody::Expr(expr) => {
+                self.out.push_str("=> ");
+                self.expr(expr);
+                self.out.push(';');
+            },
+        }
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        if statements.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.depth += 1;
+        self.statements(statements);
+        self.depth -= 1;
+        self.line_start();
+        self.out.push('}');
+    }
+
+    /// Parameters go on the line of the opening brace, ahead of the body, as in `{ a, b => ... }`.
+    fn lambda(&mut self, FunctionDecl { params, body, .. }: &FunctionDecl) {
+        let FunctionBody::Block(statements) = body else {
+            unreachable!("lambdas always have block bodies")
+        };
+        if params.is_empty() {
+            self.block(statements);
+            return;
+        }
+        let params: Vec<&str> = params.iter().map(|param| param.lexeme).collect();
+        self.out.push_str(&format!("{{ {} =>", params.join(", ")));
+        if statements.is_empty() {
+            self.out.push_str(" }");
+            return;
+        }
+        self.out.push('\n');
+        self.depth += 1;
+        self.statements(statements);
+        self.depth -= 1;
+        self.line_start();
+        self.out.push('}');
+    }
+
+    /// Blocks stay on the line of their header; any other body goes on its own indented line.
+    fn body(&mut self, body: &Statement) {
+        match body {
+            Statement::Block(block) => {
+                self.out.push(' ');
+                self.block(&block.statements);
+            },
+            _ => {
+                self.out.push('\n');
+                self.depth += 1;
+                self.line_start();
+                self.statement(body);
+                self.depth -= 1;
+            },
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Asign { name, value, .. } => {
+                self.out.push_str(name.lexeme);
+                self.out.push_str(" = ");
+                self.expr(value);
+            },
+            Expr::Binary { left, operator, right } => {
+                self.expr(left);
+                self.out.push_str(&format!(" {} ", operator.lexeme));
+                self.expr(right);
+            },
+            Expr::Unary { operator, expr } => {
+                self.out.push_str(operator.lexeme);
+                self.expr(expr);
+            },
+            Expr::Grouping { expr, .. } => {
+                self.out.push('(');
+                self.expr(expr);
+                self.out.push(')');
+            },
+            Expr::Literal { value: Literal::String(s), .. } => {
+                self.out.push('"');
+                self.string_segment(s);
+                self.out.push('"');
+            },
+            Expr::Interpolation { segments, exprs, .. } => {
+                self.out.push('"');
+                self.string_segment(&segments[0]);
+                for (expr, segment) in exprs.iter().zip(&segments[1..]) {
+                    self.out.push_str("${");
+                    self.expr(expr);
+                    self.out.push('}');
+                    self.string_segment(segment);
+                }
+                self.out.push('"');
+            },
+            Expr::Literal { value: literal, .. } => self.out.push_str(&literal.to_string()),
+            Expr::Variable { name, .. } => self.out.push_str(name.lexeme),
+            Expr::LogicalOr { left, right, .. } => {
+                self.expr(left);
+                self.out.push_str(" or ");
+                self.expr(right);
+            },
+            Expr::LogicalAnd { left, right, .. } => {
+                self.expr(left);
+                self.out.push_str(" and ");
+                self.expr(right);
+            },
+            Expr::Call { callee, args, .. } => {
+                self.expr(callee);
+                self.out.push('(');
+                match args.split_last() {
+                    Some((lambda @ Expr::Lambda { .. }, args)) => {
+                        self.comma_separated(args);
+                        self.out.push_str(") ");
+                        self.expr(lambda);
+                    },
+                    _ => {
+                        self.comma_separated(args);
+                        self.out.push(')');
+                    },
+                }
+            },
+            Expr::Get { object, name, optional } => {
+                self.expr(object);
+                self.out.push_str(if *optional { "?." } else { "." });
+                self.out.push_str(name.lexeme);
+            },
+            Expr::Set { object, name, value } => {
+                self.expr(object);
+                self.out.push('.');
+                self.out.push_str(name.lexeme);
+                self.out.push_str(" = ");
+                self.expr(value);
+            },
+            Expr::This { .. } => self.out.push_str("this"),
+            Expr::Super { method, .. } => {
+                self.out.push_str("super.");
+                self.out.push_str(method.lexeme);
+            },
+            Expr::List { elements, .. } => {
+                self.out.push('[');
+                self.comma_separated(elements);
+                self.out.push(']');
+            },
+            Expr::Map { entries, .. } => {
+                self.out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(key);
+                    self.out.push_str(": ");
+                    self.expr(value);
+                }
+                self.out.push('}');
+            },
+            Expr::Index { object, index, .. } => {
+                self.expr(object);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push(']');
+            },
+            Expr::IndexSet { object, index, value, .. } => {
+                self.expr(object);
+                self.out.push('[');
+                self.expr(index);
+                self.out.push_str("] = ");
+                self.expr(value);
+            },
+            Expr::Slice { object, start, end, .. } => {
+                self.expr(object);
+                self.out.push('[');
+                if let Some(start) = start {
+                    self.expr(start);
+                }
+                self.out.push(':');
+                if let Some(end) = end {
+                    self.expr(end);
+                }
+                self.out.push(']');
+            },
+            Expr::Lambda { decl } => self.lambda(decl),
+        }
+    }
+
+    /// Escapes each `$` that would otherwise start an interpolation or be read as an escape itself.
+    fn string_segment(&mut self, segment: &str) {
+        let mut previous = None;
+        let mut chars = segment.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '$' && (chars.peek() == Some(&'{') || previous == Some('\\')) {
+                self.out.push('\\');
+            }
+            self.out.push(c);
+            previous = Some(c);
+        }
+    }
+
+    fn comma_separated(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn line_start(&mut self) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+    }
+}
+
+fn is_declaration_block(stmt: &Statement) -> bool {
+    matches!(stmt, Statement::FunDecl(_) | Statement::ClassDecl(_))
+}
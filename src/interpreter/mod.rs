@@ -4,17 +4,24 @@ use crate::token::Token;
 pub mod tree_walker;
 pub mod vm;
 
-pub use tree_walker::TreeWalk;
+pub use tree_walker::{ImportHook, TreeWalk};
 
-pub use self::tree_walker::env::BoxedEnvironment;
+pub use self::tree_walker::env::{BoxedEnvironment, Environment};
 
 pub trait Evaluator<'a, 't> {
-    fn eval(&mut self, expr: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>>;
+    fn eval(&mut self, expr: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>>;
 }
 
 pub trait Interpreter<'a, 't> {
     fn interpret(&mut self, ast: &'a Statement<'t>) -> Result<'a, 't, ()>;
     fn interpret_block(&mut self, block: &'a [Statement<'t>], env: BoxedEnvironment<'a, 't>) -> Result<'a, 't, ()>;
+    /// Evaluates an expression in `env` instead of the current environment, as for an expression-bodied function.
+    fn interpret_expr(&mut self, expr: &'a Expr<'t>, env: BoxedEnvironment<'a, 't>) -> Result<'a, 't, Value<'a, 't>>;
+    /// Calls a function, native or class with arguments that were already evaluated, as natives calling back
+    /// into Lox do. `paren` is the token errors about the call are reported at.
+    fn call(&mut self, callee: &Value<'a, 't>, paren: &Token<'t>, args: Vec<Value<'a, 't>>) -> Result<'a, 't, Value<'a, 't>>;
+    /// Renders a value the way `print` shows it.
+    fn display(&self, value: &Value) -> String;
 }
 
 use thiserror::Error;
@@ -25,18 +32,65 @@ pub type Result<'a, 't, T> = anyhow::Result<T, RuntimeError<'a, 't>>;
 pub enum RuntimeError<'a, 't> {
     #[error("{message}\n[line {}]", operator.pos.line)]
     IncompatibleOperandType { operator: Token<'t>, message: String },
+    #[error("Division by zero.\n[line {}]", operator.pos.line)]
+    DivisionByZero { operator: Token<'t> },
     #[error("Undefined variable '{}'.\n[line {}]", token.lexeme, token.pos.line)]
     UndefinedVariable { token: Token<'t> },
     #[error("Can only call functions and classes.\n[line {}]", token.pos.line)]
     NotValidCallable { token: Token<'t> },
-    #[error("Expected {expected} arguments but got {actual}.\n[line {}]", token.pos.line)]
-    InvalidArgumentCount { token: Token<'t>, expected: usize, actual: usize },
+    #[error("Expected {expected} arguments but got {actual} in call to '{callee}'.\n[line {}]", token.pos.line)]
+    InvalidArgumentCount { token: Token<'t>, callee: String, expected: usize, actual: usize },
     #[error("Only instances have properties.\n[line {}]", token.pos.line)]
     NotAnInstance { token: Token<'t> },
     #[error("Undefined property '{}'.\n[line {}]", token.lexeme, token.pos.line)]
     UndefinedProperty { token: Token<'t> },
+    #[error("Undefined method '{}' in superclass '{superclass}'.\n[line {}]", token.lexeme, token.pos.line)]
+    UndefinedSuperMethod { token: Token<'t>, superclass: String },
+    #[error("No instance is bound to 'this'.\n[line {}]", token.pos.line)]
+    UnboundThis { token: Token<'t> },
+    #[error("No superclass is bound to 'super'.\n[line {}]", token.pos.line)]
+    UnboundSuper { token: Token<'t> },
     #[error("Superclass must be a class.\n[line {}]", token.pos.line)]
     SuperclassMustBeAClass { token: Token<'t> },
+    #[error("Only lists, maps and instances can be indexed.\n[line {}]", token.pos.line)]
+    NotIndexable { token: Token<'t> },
+    #[error("Instances must define a '{method}' method to be indexed.\n[line {}]", token.pos.line)]
+    MissingIndexMethod { token: Token<'t>, method: &'static str },
+    #[error("Index must be an integer.\n[line {}]", token.pos.line)]
+    InvalidIndex { token: Token<'t> },
+    #[error("Index out of range.\n[line {}]", token.pos.line)]
+    IndexOutOfRange { token: Token<'t> },
+    #[error("Can only destructure lists.\n[line {}]", token.pos.line)]
+    NotDestructurable { token: Token<'t> },
+    #[error("Expected {expected} values to destructure but got {actual}.\n[line {}]", token.pos.line)]
+    DestructureMismatch { token: Token<'t>, expected: usize, actual: usize },
+    #[error("{message}\n[line {}]", token.pos.line)]
+    NativeError { token: Token<'t>, message: String },
+    #[error("Value bound by 'using' must be an instance with a 'close' method.\n[line {}]", keyword.pos.line)]
+    NotClosable { keyword: Token<'t> },
+    #[error("{value}\n[line {}]", keyword.pos.line)]
+    UserError { keyword: Token<'t>, value: Value<'a, 't> },
+    #[error("{message}\n[line {}]", keyword.pos.line)]
+    ImportFailed { keyword: Token<'t>, message: String },
+    #[error("Exceeded the limit of {limit} executed statements.")]
+    StepLimitExceeded { limit: usize },
+    #[error("Exceeded the limit of {limit} variables in one scope.")]
+    ScopeLimitExceeded { limit: usize },
     #[error("")]
     Return(Option<Value<'a, 't>>),
+    /// Unwinds to the innermost loop, or to the enclosing loop with this label.
+    #[error("")]
+    Break(Option<&'t str>),
+    #[error("")]
+    Continue(Option<&'t str>),
+}
+
+impl RuntimeError<'_, '_> {
+    pub fn message(&self) -> String {
+        let rendered = self.to_string();
+        match rendered.split_once('\n') {
+            Some((message, _)) => message.to_string(),
+            None => rendered,
+        }
+    }
 }
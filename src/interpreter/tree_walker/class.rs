@@ -34,6 +34,10 @@ impl<'a, 't> Class<'a, 't> {
         Ok(Value::Instance(instance))
     }
 
+    pub fn name(&self) -> &'t str {
+        self.name
+    }
+
     pub fn arity(&self) -> usize {
         self.method("init").map(|init| init.arity()).unwrap_or(0)
     }
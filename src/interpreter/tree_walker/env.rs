@@ -9,10 +9,17 @@ use crate::token::Token;
 pub type BoxedEnvironment<'a, 't> = Rc<RefCell<Environment<'a, 't>>>;
 pub type ValueMap<'a, 't> = HashMap<String, Value<'a, 't>>;
 
+/// How many bindings a scope holds at most unless told otherwise, far more than any program written by hand declares.
+pub const DEFAULT_SCOPE_LIMIT: usize = 1_000_000;
+
 #[derive(Debug, Clone)]
 pub struct Environment<'a, 't> {
     values: ValueMap<'a, 't>,
     pub enclosing: Option<BoxedEnvironment<'a, 't>>,
+    /// Whether this is the namespace of a script imported with `as`, which holds the script's globals.
+    is_namespace: bool,
+    /// How many bindings this scope may hold. Scopes take the limit of the scope enclosing them.
+    limit: usize,
 }
 
 impl<'a, 't> Environment<'a, 't> {
@@ -20,6 +27,8 @@ impl<'a, 't> Environment<'a, 't> {
         Self {
             values: ValueMap::new(),
             enclosing: None,
+            is_namespace: false,
+            limit: DEFAULT_SCOPE_LIMIT,
         }
     }
 
@@ -31,6 +40,19 @@ impl<'a, 't> Environment<'a, 't> {
         BoxedEnvironment::new(RefCell::new(Self {
             values: ValueMap::new(),
             enclosing: Some(enclosing.clone()),
+            is_namespace: false,
+            limit: enclosing.borrow().limit,
+        }))
+    }
+
+    /// A namespace for an imported script. Names it doesn't declare are still found in `globals`, where
+    /// the natives are.
+    pub fn boxed_namespace(globals: &BoxedEnvironment<'a, 't>) -> BoxedEnvironment<'a, 't> {
+        BoxedEnvironment::new(RefCell::new(Self {
+            values: ValueMap::new(),
+            enclosing: Some(globals.clone()),
+            is_namespace: true,
+            limit: globals.borrow().limit,
         }))
     }
 }
@@ -42,42 +64,75 @@ impl Default for Environment<'_, '_> {
 }
 
 impl<'a, 't> Environment<'a, 't> {
+    /// Limits the bindings of this scope and of the scopes created inside it later, to at least one.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+    }
+
     pub fn enclosing(&self) -> Option<BoxedEnvironment<'a, 't>> {
         self.enclosing.clone()
     }
 
+    pub fn is_namespace(&self) -> bool {
+        self.is_namespace
+    }
+
+    pub fn bindings(&self) -> &ValueMap<'a, 't> {
+        &self.values
+    }
+
     pub fn get(&self, name: &str) -> Option<Value<'a, 't>> {
-        match self.values.get(name) {
-            Some(value) => Some(value.clone()),
-            None => match &self.enclosing {
-                Some(enclosing) => enclosing.borrow().get(name).clone(),
-                None => None,
-            },
+        if let Some(value) = self.values.get(name) {
+            return Some(value.clone());
+        }
+        let mut current = self.enclosing.clone();
+        while let Some(env) = current {
+            let env = env.borrow();
+            if let Some(value) = env.values.get(name) {
+                return Some(value.clone());
+            }
+            current = env.enclosing.clone();
         }
+        None
     }
 
     pub fn get_at(&self, name: &str, height: usize) -> Option<Value<'a, 't>> {
         match height {
             0 => self.values.get(name).cloned(),
-            h => self.enclosing.as_ref().and_then(|e| e.borrow().get_at(name, h - 1).clone()),
+            h => self.ancestor(h)?.borrow().values.get(name).cloned(),
         }
     }
 
-    pub fn define(&mut self, name: impl Into<String>, value: Value<'a, 't>) {
-        self.values.insert(name.into(), value);
+    /// Defines `name` in this scope, failing when a new binding would take the scope past its limit.
+    pub fn define(&mut self, name: impl Into<String>, value: Value<'a, 't>) -> Result<(), RuntimeError<'a, 't>> {
+        self.define_checked(name, value).map(|_| ())
+    }
+
+    /// Defines `name` in this scope and returns whether that replaced a binding this scope already had.
+    /// Bindings of the same name in enclosing scopes are shadowed, not replaced, so they don't count.
+    pub fn define_checked(&mut self, name: impl Into<String>, value: Value<'a, 't>) -> Result<bool, RuntimeError<'a, 't>> {
+        let name = name.into();
+        if self.values.len() >= self.limit && !self.values.contains_key(&name) {
+            return Err(RuntimeError::ScopeLimitExceeded { limit: self.limit });
+        }
+        Ok(self.values.insert(name, value).is_some())
     }
 
     pub fn assign(&mut self, name: Token<'t>, value: Value<'a, 't>) -> Result<(), RuntimeError<'a, 't>> {
-        match self.values.get_mut(name.lexeme) {
-            Some(existing_value) => {
+        if let Some(existing_value) = self.values.get_mut(name.lexeme) {
+            *existing_value = value;
+            return Ok(());
+        }
+        let mut current = self.enclosing.clone();
+        while let Some(env) = current {
+            let mut env = env.borrow_mut();
+            if let Some(existing_value) = env.values.get_mut(name.lexeme) {
                 *existing_value = value;
-                Ok(())
-            },
-            None => match &mut self.enclosing {
-                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
-                None => Err(RuntimeError::UndefinedVariable { token: name }),
-            },
+                return Ok(());
+            }
+            current = env.enclosing.clone();
         }
+        Err(RuntimeError::UndefinedVariable { token: name })
     }
 
     pub fn assign_at(&mut self, name: Token<'t>, value: Value<'a, 't>, height: usize) {
@@ -86,8 +141,42 @@ impl<'a, 't> Environment<'a, 't> {
                 self.values.insert(name.lexeme.to_string(), value);
             },
             h => {
-                self.enclosing.as_ref().map(|e| e.borrow_mut().assign_at(name, value, h - 1));
+                if let Some(env) = self.ancestor(h) {
+                    env.borrow_mut().values.insert(name.lexeme.to_string(), value);
+                }
             },
         };
     }
+
+    /// Walks `height` (at least 1) environments up the chain, holding one borrow at a time.
+    fn ancestor(&self, height: usize) -> Option<BoxedEnvironment<'a, 't>> {
+        let mut env = self.enclosing.clone()?;
+        for _ in 1..height {
+            let enclosing = env.borrow().enclosing.clone()?;
+            env = enclosing;
+        }
+        Some(env)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn define_checked_reports_a_name_redefined_in_the_same_scope() {
+        let env = Environment::boxed();
+        assert!(!env.borrow_mut().define_checked("x", Value::Number(1.0)).unwrap());
+        assert!(env.borrow_mut().define_checked("x", Value::Number(2.0)).unwrap());
+        assert_eq!(env.borrow().get("x"), Some(Value::Number(2.0)));
+    }
+
+    #[test]
+    fn define_checked_does_not_report_shadowing_an_enclosing_scope() {
+        let outer = Environment::boxed();
+        outer.borrow_mut().define("x", Value::Number(1.0)).unwrap();
+        let inner = Environment::boxed_with_enclosing(&outer);
+        assert!(!inner.borrow_mut().define_checked("x", Value::Number(2.0)).unwrap());
+        assert_eq!(outer.borrow().get("x"), Some(Value::Number(1.0)));
+    }
 }
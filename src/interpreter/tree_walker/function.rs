@@ -1,12 +1,14 @@
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::fmt::{Debug, Display};
 use std::rc::Rc;
 use std::time::SystemTime;
 
+use super::as_integer;
 use super::env::{BoxedEnvironment, Environment};
 use super::instance::Instance;
 use super::super::{Interpreter, RuntimeError};
-use crate::syntax::{FunctionDecl, Statement, Value};
+use crate::syntax::{FunctionBody, FunctionDecl, Value};
 use crate::token::Token;
 
 pub enum FunctionType {
@@ -27,9 +29,10 @@ impl Display for FunctionType {
 pub struct Function<'a, 't> {
     name: Token<'t>,
     params: Vec<Token<'t>>,
-    body: &'a [Statement<'t>],
+    body: &'a FunctionBody<'t>,
     closure: BoxedEnvironment<'a, 't>,
     is_init: bool,
+    is_getter: bool,
 }
 
 impl<'a, 't> Function<'a, 't> {
@@ -40,6 +43,7 @@ impl<'a, 't> Function<'a, 't> {
             body: &decl.body,
             closure: env,
             is_init,
+            is_getter: decl.is_getter,
         }
     }
 }
@@ -47,12 +51,14 @@ impl<'a, 't> Function<'a, 't> {
 impl<'a, 't> Function<'a, 't> {
     pub fn bind(&self, instance: &Rc<RefCell<Instance<'a, 't>>>) -> Self {
         let binded_env = Environment::boxed_with_enclosing(&self.closure);
-        binded_env.borrow_mut().define("this", Value::Instance(Rc::clone(instance)));
+        // A new scope always has room for one binding
+        binded_env.borrow_mut().define("this", Value::Instance(Rc::clone(instance))).unwrap();
         Self {
             name: self.name.clone(),
             params: self.params.clone(),
             body: self.body,
             is_init: self.is_init,
+            is_getter: self.is_getter,
             closure: binded_env,
         }
     }
@@ -69,17 +75,33 @@ impl<'a, 't> Function<'a, 't> {
         let environment = Environment::boxed_with_enclosing(&self.closure);
         let mut args = args.into_iter();
         for param in &self.params {
-            environment.borrow_mut().define(param.lexeme, args.next().unwrap());
+            environment.borrow_mut().define(param.lexeme, args.next().unwrap())?;
         }
-        match interpreter.interpret_block(self.body, environment) {
-            Ok(_) if self.is_init => Ok(self.closure.borrow().get("this").unwrap()),
-            Ok(_) => Ok(Value::Nil),
-            Err(RuntimeError::Return(_)) if self.is_init => Ok(self.closure.borrow().get("this").unwrap()),
+        let result = match self.body {
+            FunctionBody::Block(statements) => interpreter.interpret_block(statements, environment).map(|_| Value::Nil),
+            FunctionBody::Expr(expr) => interpreter.interpret_expr(expr, environment),
+        };
+        match result {
+            Ok(_) | Err(RuntimeError::Return(_)) if self.is_init => self.this(),
+            Ok(value) => Ok(value),
             Err(RuntimeError::Return(value)) => Ok(value.unwrap_or(Value::Nil)),
             Err(e) => Err(e),
         }
     }
 
+    /// An initializer always returns the instance it is bound to.
+    fn this(&self) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>> {
+        self.closure.borrow().get("this").ok_or(RuntimeError::UnboundThis { token: self.name })
+    }
+
+    pub fn name(&self) -> &'t str {
+        self.name.lexeme
+    }
+
+    pub fn is_getter(&self) -> bool {
+        self.is_getter
+    }
+
     pub fn arity(&self) -> usize {
         self.params.len()
     }
@@ -102,21 +124,178 @@ impl Display for Function<'_, '_> {
     }
 }
 
+pub type NativeResult<'a, 't> = std::result::Result<Value<'a, 't>, String>;
+
+/// A native that calls back into Lox, like one applying a function it was passed. Errors raised by the
+/// code it calls propagate unchanged, so its own errors are built from the call's closing paren.
+pub type InterpreterNative<'a, 't> = fn(&mut dyn Interpreter<'a, 't>, &Token<'t>, Vec<Value<'a, 't>>) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>>;
+
+#[derive(Debug, Clone, Copy)]
+enum Native<'a, 't> {
+    Plain(fn(Vec<Value<'a, 't>>) -> NativeResult<'a, 't>),
+    WithInterpreter(InterpreterNative<'a, 't>),
+}
+
 #[derive(Debug, Clone)]
 pub struct NativeFunction<'a, 't> {
     pub name: &'static str,
     pub arity: usize,
-    native: fn(Vec<Value<'a, 't>>) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>>,
+    /// How many arguments may follow the required ones; the native sees only the arguments actually passed.
+    pub optional: usize,
+    native: Native<'a, 't>,
 }
 
 impl<'a, 't> NativeFunction<'a, 't> {
-    pub fn new(name: &'static str, arity: usize, native: fn(Vec<Value<'a, 't>>) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>>) -> Self {
-        Self { name, arity, native }
+    pub fn new(name: &'static str, arity: usize, native: fn(Vec<Value<'a, 't>>) -> NativeResult<'a, 't>) -> Self {
+        Self {
+            name,
+            arity,
+            optional: 0,
+            native: Native::Plain(native),
+        }
+    }
+
+    pub fn with_interpreter(name: &'static str, arity: usize, native: InterpreterNative<'a, 't>) -> Self {
+        Self {
+            name,
+            arity,
+            optional: 0,
+            native: Native::WithInterpreter(native),
+        }
+    }
+
+    pub fn optional(self, optional: usize) -> Self {
+        Self { optional, ..self }
+    }
+
+    /// Lets any number of arguments follow the required ones.
+    pub fn variadic(self) -> Self {
+        let optional = usize::MAX - self.arity;
+        self.optional(optional)
     }
 
     pub fn clock() -> Self {
         return Self::new("clock", 0, clock);
     }
+
+    pub fn len() -> Self {
+        Self::new("len", 1, len)
+    }
+
+    pub fn keys() -> Self {
+        Self::new("keys", 1, keys)
+    }
+
+    pub fn values() -> Self {
+        Self::new("values", 1, values)
+    }
+
+    pub fn push() -> Self {
+        Self::new("push", 2, push)
+    }
+
+    pub fn pop() -> Self {
+        Self::new("pop", 1, pop)
+    }
+
+    pub fn insert() -> Self {
+        Self::new("insert", 3, insert)
+    }
+
+    pub fn remove() -> Self {
+        Self::new("remove", 2, remove)
+    }
+
+    pub fn ord() -> Self {
+        Self::new("ord", 1, ord)
+    }
+
+    pub fn chr() -> Self {
+        Self::new("chr", 1, chr)
+    }
+
+    pub fn to_num() -> Self {
+        Self::new("to_num", 1, to_num)
+    }
+
+    pub fn range() -> Self {
+        Self::new("range", 1, range).optional(1)
+    }
+
+    pub fn min() -> Self {
+        Self::new("min", 1, min).optional(1)
+    }
+
+    pub fn max() -> Self {
+        Self::new("max", 1, max).optional(1)
+    }
+
+    pub fn abs() -> Self {
+        Self::new("abs", 1, abs)
+    }
+
+    pub fn sign() -> Self {
+        Self::new("sign", 1, sign)
+    }
+
+    pub fn round() -> Self {
+        Self::new("round", 1, round).optional(1)
+    }
+
+    pub fn gcd() -> Self {
+        Self::new("gcd", 2, gcd)
+    }
+
+    pub fn lcm() -> Self {
+        Self::new("lcm", 2, lcm)
+    }
+
+    pub fn sort() -> Self {
+        Self::with_interpreter("sort", 1, sort).optional(1)
+    }
+
+    pub fn format() -> Self {
+        Self::with_interpreter("format", 1, format).variadic()
+    }
+
+    pub fn assert_eq() -> Self {
+        Self::new("assert_eq", 2, assert_eq)
+    }
+
+    pub fn is_nil() -> Self {
+        Self::new("is_nil", 1, |args| Ok(Value::Bool(matches!(args[0], Value::Nil))))
+    }
+
+    pub fn is_number() -> Self {
+        Self::new("is_number", 1, |args| Ok(Value::Bool(matches!(args[0], Value::Number(_)))))
+    }
+
+    pub fn is_string() -> Self {
+        Self::new("is_string", 1, |args| Ok(Value::Bool(matches!(args[0], Value::String(_)))))
+    }
+
+    pub fn is_bool() -> Self {
+        Self::new("is_bool", 1, |args| Ok(Value::Bool(matches!(args[0], Value::Bool(_)))))
+    }
+
+    /// Classes are callable too, since calling one constructs an instance.
+    pub fn is_callable() -> Self {
+        Self::new("is_callable", 1, |args| {
+            Ok(Value::Bool(matches!(args[0], Value::Function(_) | Value::NativeFunction(_) | Value::Class(_))))
+        })
+    }
+
+    pub fn is_instance() -> Self {
+        Self::new("is_instance", 1, |args| Ok(Value::Bool(matches!(args[0], Value::Instance(_)))))
+    }
+
+    /// Converts a value to a string the way `print` shows it, except that instances also list their fields.
+    pub fn debug() -> Self {
+        Self::new("debug", 1, |args| match &args[0] {
+            Value::Instance(instance) => Ok(Value::String(instance.borrow().debug_string().into())),
+            value => Ok(Value::String(value.to_string().into())),
+        })
+    }
 }
 
 impl PartialEq for NativeFunction<'_, '_> {
@@ -131,13 +310,25 @@ impl PartialOrd for NativeFunction<'_, '_> {
 }
 
 impl<'a, 't> NativeFunction<'a, 't> {
-    pub fn call(&self, args: Vec<Value<'a, 't>>) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>> {
-        (self.native)(args)
+    pub fn call(
+        &self,
+        interpreter: &mut dyn Interpreter<'a, 't>,
+        paren: &Token<'t>,
+        args: Vec<Value<'a, 't>>,
+    ) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>> {
+        match self.native {
+            Native::Plain(native) => native(args).map_err(|message| RuntimeError::NativeError { token: *paren, message }),
+            Native::WithInterpreter(native) => native(interpreter, paren, args),
+        }
     }
 
     pub fn arity(&self) -> usize {
         self.arity
     }
+
+    pub fn max_arity(&self) -> usize {
+        self.arity + self.optional
+    }
 }
 
 impl Display for NativeFunction<'_, '_> {
@@ -146,7 +337,353 @@ impl Display for NativeFunction<'_, '_> {
     }
 }
 
-fn clock<'a, 't>(_args: Vec<Value<'a, 't>>) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>> {
+fn clock<'a, 't>(_args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
     let millis = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs_f64();
     Ok(Value::Number(millis))
 }
+
+// Strings are measured in Unicode scalar values, not bytes or grapheme clusters.
+fn len<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    match &args[0] {
+        Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
+        Value::List(list) => Ok(Value::Number(list.borrow().len() as f64)),
+        Value::Map(map) => Ok(Value::Number(map.borrow().len() as f64)),
+        _ => Err("Argument to 'len' must be a string, a list or a map.".to_string()),
+    }
+}
+
+// Both return a new list in the map's insertion order.
+fn keys<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    match &args[0] {
+        Value::Map(map) => {
+            let keys = map.borrow().iter().map(|(key, _)| key.clone()).collect();
+            Ok(Value::List(Rc::new(RefCell::new(keys))))
+        },
+        _ => Err("Argument to 'keys' must be a map.".to_string()),
+    }
+}
+
+fn values<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    match &args[0] {
+        Value::Map(map) => {
+            let values = map.borrow().iter().map(|(_, value)| value.clone()).collect();
+            Ok(Value::List(Rc::new(RefCell::new(values))))
+        },
+        _ => Err("Argument to 'values' must be a map.".to_string()),
+    }
+}
+
+fn push<'a, 't>(mut args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let value = args.pop().unwrap();
+    let Value::List(list) = &args[0] else {
+        return Err("First argument to 'push' must be a list.".to_string());
+    };
+    list.borrow_mut().push(value);
+    Ok(Value::Nil)
+}
+
+fn pop<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let Value::List(list) = &args[0] else {
+        return Err("Argument to 'pop' must be a list.".to_string());
+    };
+    Ok(list.borrow_mut().pop().unwrap_or(Value::Nil))
+}
+
+fn insert<'a, 't>(mut args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let value = args.pop().unwrap();
+    let Value::List(list) = &args[0] else {
+        return Err("First argument to 'insert' must be a list.".to_string());
+    };
+    let mut list = list.borrow_mut();
+    // Inserting at the length appends
+    let i = list_position(&args[1], list.len(), list.len())?;
+    list.insert(i, value);
+    Ok(Value::Nil)
+}
+
+fn remove<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let Value::List(list) = &args[0] else {
+        return Err("First argument to 'remove' must be a list.".to_string());
+    };
+    let mut list = list.borrow_mut();
+    match list.len() {
+        0 => Err("Index out of range.".to_string()),
+        len => Ok(list.remove(list_position(&args[1], len, len - 1)?)),
+    }
+}
+
+// Like `len`, these work on Unicode scalar values: `ord` reads the first one and `chr` builds a one-character string.
+fn ord<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let Value::String(s) = &args[0] else {
+        return Err("Argument to 'ord' must be a string.".to_string());
+    };
+    match s.chars().next() {
+        Some(c) => Ok(Value::Number(c as u32 as f64)),
+        None => Err("Argument to 'ord' must not be empty.".to_string()),
+    }
+}
+
+fn chr<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let code = as_integer(&args[0]).and_then(|n| u32::try_from(n).ok()).and_then(char::from_u32);
+    match code {
+        Some(c) => Ok(Value::String(c.to_string().into())),
+        None => Err("Argument to 'chr' must be a valid code point.".to_string()),
+    }
+}
+
+// Booleans become 1 and 0, and a string must hold nothing but a decimal number, give or take surrounding
+// whitespace. Words like `inf` and `NaN` aren't numbers in Lox source, so they aren't accepted either.
+fn to_num<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::Bool(b) => Ok(Value::Number(if *b { 1.0 } else { 0.0 })),
+        Value::String(s) => {
+            let s = s.trim();
+            let decimal = s.chars().all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'));
+            match s.parse() {
+                Ok(n) if decimal => Ok(Value::Number(n)),
+                _ => Err(format!("Can't convert \"{s}\" to a number.")),
+            }
+        },
+        value => Err(format!("Can't convert a value of type {} to a number.", value.type_name())),
+    }
+}
+
+/// The most numbers `range` lists; a longer list would exhaust memory instead.
+const MAX_RANGE_LEN: i64 = 1 << 26;
+
+// `range(end)` counts up from 0 and `range(start, end)` from `start`, excluding `end`. A range that
+// doesn't count up is empty rather than an error, matching how an exhausted loop runs zero times.
+fn range<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let bounds = args.iter().map(as_integer).collect::<Option<Vec<_>>>();
+    let (start, end) = match bounds.as_deref() {
+        Some(&[end]) => (0, end),
+        Some(&[start, end]) => (start, end),
+        _ => return Err("Arguments to 'range' must be integers.".to_string()),
+    };
+    if end.saturating_sub(start) > MAX_RANGE_LEN {
+        return Err(format!("A range can't have more than {MAX_RANGE_LEN} numbers."));
+    }
+    let numbers = (start..end).map(|n| Value::Number(n as f64)).collect();
+    Ok(Value::List(Rc::new(RefCell::new(numbers))))
+}
+
+fn min<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    extreme("min", args, Ordering::Less)
+}
+
+fn max<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    extreme("max", args, Ordering::Greater)
+}
+
+/// Picks the value ordered first by `wanted` among two arguments or the elements of a single list
+/// argument. The values must be all numbers or all strings; on ties the earliest one wins.
+fn extreme<'a, 't>(name: &str, args: Vec<Value<'a, 't>>, wanted: Ordering) -> NativeResult<'a, 't> {
+    let values = match args.as_slice() {
+        [Value::List(list)] => list.borrow().clone(),
+        [_] => return Err(format!("Argument to '{name}' must be a list when it is the only one.")),
+        _ => args,
+    };
+    let comparable = values.iter().all(|value| matches!(value, Value::Number(_))) || values.iter().all(|value| matches!(value, Value::String(_)));
+    if !comparable {
+        return Err(format!("Values passed to '{name}' must be all numbers or all strings."));
+    }
+    values
+        .into_iter()
+        .reduce(|best, value| if value.partial_cmp(&best) == Some(wanted) { value } else { best })
+        .ok_or_else(|| format!("Argument to '{name}' must not be an empty list."))
+}
+
+fn abs<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    match args[0] {
+        Value::Number(n) => Ok(Value::Number(n.abs())),
+        _ => Err("Argument to 'abs' must be a number.".to_string()),
+    }
+}
+
+// -1, 0 or 1; zero has no sign, whichever zero it is, and NaN stays NaN.
+fn sign<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    match args[0] {
+        Value::Number(n) if n == 0.0 || n.is_nan() => Ok(Value::Number(n.abs())),
+        Value::Number(n) => Ok(Value::Number(n.signum())),
+        _ => Err("Argument to 'sign' must be a number.".to_string()),
+    }
+}
+
+/// Rounds half away from zero to a number of decimal places, none unless a second argument says otherwise.
+/// A negative number of places rounds to tens, hundreds and so on.
+fn round<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let Value::Number(x) = args[0] else {
+        return Err("First argument to 'round' must be a number.".to_string());
+    };
+    let digits = match args.get(1) {
+        None => 0,
+        Some(digits) => as_integer(digits).ok_or_else(|| "Number of digits passed to 'round' must be an integer.".to_string())?,
+    };
+    Ok(Value::Number(round_to(x, digits)))
+}
+
+fn round_to(x: f64, digits: i64) -> f64 {
+    // Doubles range from about 1e-324 to 1e308, so more places than that change nothing
+    let factor = 10f64.powi(digits.unsigned_abs().min(400) as i32);
+    if digits < 0 {
+        return match factor.is_finite() {
+            true => (x / factor).round() * factor,
+            false => x * 0.0,
+        };
+    }
+    let scaled = x * factor;
+    // Scaled past 2^52 a double has no fraction left to round, and dividing back would only add error
+    if !scaled.is_finite() || scaled.abs() >= 2f64.powi(52) {
+        return x;
+    }
+    scaled.round() / factor
+}
+
+// Both are never negative, whatever the signs of the arguments, and `gcd(0, 0)` and `lcm(0, n)` are 0.
+fn gcd<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let (a, b) = integer_pair("gcd", &args)?;
+    Ok(Value::Number(greatest_common_divisor(a, b) as f64))
+}
+
+fn lcm<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let (a, b) = integer_pair("lcm", &args)?;
+    match greatest_common_divisor(a, b) {
+        0 => Ok(Value::Number(0.0)),
+        divisor => Ok(Value::Number((a / divisor * b) as f64)),
+    }
+}
+
+fn integer_pair(name: &str, args: &[Value]) -> Result<(u128, u128), String> {
+    match (as_integer(&args[0]), as_integer(&args[1])) {
+        (Some(a), Some(b)) => Ok((a.unsigned_abs() as u128, b.unsigned_abs() as u128)),
+        _ => Err(format!("Arguments to '{name}' must be integers.")),
+    }
+}
+
+fn greatest_common_divisor(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Sorts a list in place and stably, by a comparator `(a, b)` returning a negative number, zero or a
+/// positive number when one is passed, and otherwise by the natural order of numbers, strings or instances
+/// with a `compare` method.
+fn sort<'a, 't>(interpreter: &mut dyn Interpreter<'a, 't>, paren: &Token<'t>, args: Vec<Value<'a, 't>>) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>> {
+    let Value::List(list) = &args[0] else {
+        return Err(RuntimeError::NativeError {
+            token: *paren,
+            message: "First argument to 'sort' must be a list.".to_string(),
+        });
+    };
+    // Lox code run by the comparison may use the list, so a copy is sorted without holding a borrow
+    let mut items = list.borrow().clone();
+    let mut error = None;
+    items.sort_by(|a, b| {
+        if error.is_some() {
+            return Ordering::Equal;
+        }
+        order(interpreter, paren, args.get(1), a, b).unwrap_or_else(|e| {
+            error = Some(e);
+            Ordering::Equal
+        })
+    });
+    if let Some(error) = error {
+        return Err(error);
+    }
+    *list.borrow_mut() = items;
+    Ok(Value::Nil)
+}
+
+fn order<'a, 't>(
+    interpreter: &mut dyn Interpreter<'a, 't>,
+    paren: &Token<'t>,
+    comparator: Option<&Value<'a, 't>>,
+    a: &Value<'a, 't>,
+    b: &Value<'a, 't>,
+) -> anyhow::Result<Ordering, RuntimeError<'a, 't>> {
+    let native_error = |message: &str| RuntimeError::NativeError {
+        token: *paren,
+        message: message.to_string(),
+    };
+    if let Some(comparator) = comparator {
+        return match interpreter.call(comparator, paren, vec![a.clone(), b.clone()])? {
+            Value::Number(n) => Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+            _ => Err(native_error("Comparator passed to 'sort' must return a number.")),
+        };
+    }
+    let ordering = match (a, b) {
+        (Value::Number(_), Value::Number(_)) | (Value::String(_), Value::String(_)) => Some(a.partial_cmp(b).unwrap_or(Ordering::Equal)),
+        (Value::Instance(instance), _) => Instance::compare(instance, interpreter, paren, b.clone())?,
+        _ => None,
+    };
+    ordering.ok_or_else(|| native_error("Values passed to 'sort' must be all numbers, all strings or instances with a 'compare' method."))
+}
+
+/// Replaces each `{}` in the format string with the next argument, shown the way `print` shows it. `{{`
+/// and `}}` stand for literal braces, and there must be exactly one argument per placeholder.
+fn format<'a, 't>(
+    interpreter: &mut dyn Interpreter<'a, 't>,
+    paren: &Token<'t>,
+    args: Vec<Value<'a, 't>>,
+) -> anyhow::Result<Value<'a, 't>, RuntimeError<'a, 't>> {
+    let native_error = |message: String| RuntimeError::NativeError { token: *paren, message };
+    let Value::String(template) = &args[0] else {
+        return Err(native_error("First argument to 'format' must be a string.".to_string()));
+    };
+    let values = &args[1..];
+    let mut output = String::new();
+    let mut placeholders = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                output.push(c);
+            },
+            ('{', Some('}')) => {
+                chars.next();
+                if let Some(value) = values.get(placeholders) {
+                    output.push_str(&interpreter.display(value));
+                }
+                placeholders += 1;
+            },
+            ('{' | '}', _) => return Err(native_error(format!("Unmatched '{c}' in format string."))),
+            _ => output.push(c),
+        }
+    }
+    if placeholders != values.len() {
+        return Err(native_error(format!(
+            "Format string expects {placeholders} arguments but got {}.",
+            values.len()
+        )));
+    }
+    Ok(Value::String(output.into()))
+}
+
+// Both values are shown with their types, since `3` and `"3"` print the same but are never equal.
+fn assert_eq<'a, 't>(args: Vec<Value<'a, 't>>) -> NativeResult<'a, 't> {
+    let (expected, actual) = (&args[0], &args[1]);
+    if expected == actual {
+        return Ok(Value::Nil);
+    }
+    let describe = |value: &Value| match value {
+        Value::String(s) => format!("\"{s}\" (string)"),
+        value => format!("{value} ({})", value.type_name()),
+    };
+    Err(format!("assertion failed: expected {}, got {}", describe(expected), describe(actual)))
+}
+
+/// Resolves an index up to `last` the way indexing does, counting negative indices from the end.
+fn list_position(index: &Value, len: usize, last: usize) -> Result<usize, String> {
+    let Some(n) = as_integer(index) else {
+        return Err("Index must be an integer.".to_string());
+    };
+    let i = if n < 0 { n + len as i64 } else { n };
+    match 0 <= i && i <= last as i64 {
+        true => Ok(i as usize),
+        false => Err("Index out of range.".to_string()),
+    }
+}
@@ -1,22 +1,31 @@
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::rc::Rc;
 
 use super::class::Class;
-use crate::interpreter::RuntimeError;
+use super::function::Function;
+use crate::interpreter::{Interpreter, RuntimeError};
 use crate::syntax::Value;
 use crate::token::Token;
 
 #[derive(Debug, Clone)]
 pub struct Instance<'a, 't> {
     class: Rc<Class<'a, 't>>,
-    fields: HashMap<&'t str, Value<'a, 't>>,
+    /// Fields in the order they were first assigned, so anything listing them is deterministic.
+    fields: Vec<(&'t str, Value<'a, 't>)>,
+    /// Position of each field in `fields`.
+    index: HashMap<&'t str, usize>,
 }
 
 impl<'a, 't> Instance<'a, 't> {
     pub fn new(class: Rc<Class<'a, 't>>) -> Self {
-        Self { class, fields: HashMap::new() }
+        Self {
+            class,
+            fields: vec![],
+            index: HashMap::new(),
+        }
     }
 
     pub fn boxed(class: Rc<Class<'a, 't>>) -> Rc<RefCell<Self>> {
@@ -26,7 +35,7 @@ impl<'a, 't> Instance<'a, 't> {
 
 impl<'a, 't> Instance<'a, 't> {
     pub fn get(this: &Rc<RefCell<Self>>, name: &Token<'t>) -> Result<Value<'a, 't>, RuntimeError<'a, 't>> {
-        if let Some(field) = this.borrow().fields.get(name.lexeme) {
+        if let Some(field) = this.borrow().field(name.lexeme) {
             return Ok(field).cloned();
         }
         if let Some(method) = this.borrow().class.method(&name.lexeme) {
@@ -35,8 +44,53 @@ impl<'a, 't> Instance<'a, 't> {
         Err(RuntimeError::UndefinedProperty { token: name.clone() })
     }
 
+    /// Orders the instance before or after `other` by calling its class's `compare(other)` method, which
+    /// returns a negative number, zero or a positive number. Returns `None` when the class has no such method.
+    pub fn compare(
+        this: &Rc<RefCell<Self>>,
+        interpreter: &mut dyn Interpreter<'a, 't>,
+        token: &Token<'t>,
+        other: Value<'a, 't>,
+    ) -> Result<Option<Ordering>, RuntimeError<'a, 't>> {
+        let Some(compare) = this.borrow().method("compare") else {
+            return Ok(None);
+        };
+        match interpreter.call(&Value::Function(Rc::new(compare.bind(this))), token, vec![other])? {
+            Value::Number(n) => Ok(Some(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal))),
+            _ => Err(RuntimeError::IncompatibleOperandType {
+                operator: *token,
+                message: "Method 'compare' must return a number.".to_string(),
+            }),
+        }
+    }
+
+    pub fn method(&self, name: &str) -> Option<Rc<Function<'a, 't>>> {
+        self.class.method(name)
+    }
+
     pub fn set(&mut self, name: &'t str, value: Value<'a, 't>) {
-        self.fields.insert(name, value);
+        match self.index.get(name) {
+            Some(&i) => self.fields[i].1 = value,
+            None => {
+                self.index.insert(name, self.fields.len());
+                self.fields.push((name, value));
+            },
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<&Value<'a, 't>> {
+        self.index.get(name).map(|&i| &self.fields[i].1)
+    }
+
+    /// Renders the instance with its fields, like `Point instance { x: 1, y: 2 }`, for debugging.
+    ///
+    /// Fields are listed in the order they were first assigned and printed with their regular display, so nested instances aren't expanded.
+    pub fn debug_string(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|(name, value)| format!("{name}: {value}")).collect();
+        match fields.is_empty() {
+            true => format!("{self} {{}}"),
+            false => format!("{self} {{ {} }}", fields.join(", ")),
+        }
     }
 }
 
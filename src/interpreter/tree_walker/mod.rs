@@ -1,35 +1,207 @@
-use std::cell::Cell;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::slice;
 
 pub mod class;
 pub mod env;
 pub mod function;
 pub mod instance;
+pub mod module;
 
 use self::class::Class;
-use self::env::{BoxedEnvironment, Environment};
+use self::env::{BoxedEnvironment, Environment, DEFAULT_SCOPE_LIMIT};
 use self::function::{Function, NativeFunction};
 use self::instance::Instance;
+use self::module::Module;
 use super::{Evaluator, Interpreter, Result, RuntimeError};
+use crate::formatter;
 use crate::syntax::{
-    ClassDecl, Expr, ExpressionStatement, FunctionDecl, IfStatemnet, PrintStatement, ReturnStatement, Statement, Value, VariableDecl, WhileStatement,
+    self, BlockStatement, CasePattern, CatchClause, ClassDecl, DestructureDecl, Expr, ExpressionStatement, FunctionDecl, IfStatemnet, ImportStatement, Literal,
+    LoopJump, NumberFormat, PrintStatement, ReturnStatement, Statement, SwitchStatement, ThrowStatement, TryStatement, UsingStatement, Value, ValueVisitor,
+    VariableDecl, WhileStatement, SCIENTIFIC_BELOW, SCIENTIFIC_FROM,
 };
-use crate::token::{Token, TokenType};
+use crate::token::{Token, TokenLiteral, TokenType};
+
+/// The longest string, in bytes, that repetition builds; anything longer would exhaust memory instead.
+const MAX_STRING_LEN: usize = 1 << 30;
+
+pub type TraceHook<'a, 't> = Box<dyn FnMut(&Statement<'t>, &Environment<'a, 't>)>;
+
+/// Scans, parses and resolves the script at a path for `import`, or says why it can't be imported.
+pub type ImportHook<'a, 't> = Box<dyn FnMut(&Path) -> std::result::Result<&'a [Statement<'t>], String>>;
+
+/// A call that hasn't returned yet: what was called and the line it was called from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StackFrame<'t> {
+    pub name: &'t str,
+    pub line: u64,
+}
 
 pub struct TreeWalk<'a, 't> {
+    /// The natives and the globals the host sets, enclosing the globals so they don't count toward its limit.
+    builtins: BoxedEnvironment<'a, 't>,
     globals: BoxedEnvironment<'a, 't>,
     environment: BoxedEnvironment<'a, 't>,
+    number_format: NumberFormat,
+    step_limit: Option<usize>,
+    steps: usize,
+    trace_hook: Option<TraceHook<'a, 't>>,
+    trace: bool,
+    call_stack: Vec<StackFrame<'t>>,
+    /// The call stack as it was when the error being propagated left its innermost call.
+    error_trace: Vec<StackFrame<'t>>,
+    import_hook: Option<ImportHook<'a, 't>>,
+    /// The scope each script that was run or imported ran in, so none runs twice.
+    imported: HashMap<PathBuf, BoxedEnvironment<'a, 't>>,
+    /// The scripts whose top-level code is running, the innermost import last.
+    importing: Vec<PathBuf>,
+    /// Names declared again in the globals since [`TreeWalk::take_redefined`] last ran.
+    redefined: Vec<Token<'t>>,
 }
 
-impl TreeWalk<'_, '_> {
+impl<'a, 't> TreeWalk<'a, 't> {
     pub fn new() -> Self {
-        let globals = Environment::boxed();
-        globals.borrow_mut().define("clock", Value::NativeFunction(Rc::new(NativeFunction::clock())));
+        let builtins = Environment::boxed();
+        for native in [
+            NativeFunction::clock(),
+            NativeFunction::len(),
+            NativeFunction::keys(),
+            NativeFunction::values(),
+            NativeFunction::push(),
+            NativeFunction::pop(),
+            NativeFunction::insert(),
+            NativeFunction::remove(),
+            NativeFunction::ord(),
+            NativeFunction::chr(),
+            NativeFunction::to_num(),
+            NativeFunction::range(),
+            NativeFunction::min(),
+            NativeFunction::max(),
+            NativeFunction::abs(),
+            NativeFunction::sign(),
+            NativeFunction::round(),
+            NativeFunction::gcd(),
+            NativeFunction::lcm(),
+            NativeFunction::sort(),
+            NativeFunction::format(),
+            NativeFunction::assert_eq(),
+            NativeFunction::is_nil(),
+            NativeFunction::is_number(),
+            NativeFunction::is_string(),
+            NativeFunction::is_bool(),
+            NativeFunction::is_callable(),
+            NativeFunction::is_instance(),
+            NativeFunction::debug(),
+        ] {
+            let name = native.name;
+            // A new scope is far from any limit
+            builtins.borrow_mut().define(name, Value::NativeFunction(Rc::new(native))).unwrap();
+        }
+        let globals = Environment::boxed_with_enclosing(&builtins);
         Self {
             environment: BoxedEnvironment::clone(&globals),
+            builtins,
             globals,
+            number_format: NumberFormat::default(),
+            step_limit: None,
+            steps: 0,
+            trace_hook: None,
+            trace: false,
+            call_stack: vec![],
+            error_trace: vec![],
+            import_hook: None,
+            imported: HashMap::new(),
+            importing: vec![],
+            redefined: vec![],
         }
     }
+
+    /// Makes the globals the current scope again. Every scope entered while running a statement is left
+    /// even when it fails, so this only guards a REPL against a failure leaving a nested scope behind.
+    pub fn reset_scope(&mut self) {
+        self.environment = BoxedEnvironment::clone(&self.globals);
+        self.error_trace.clear();
+    }
+
+    /// The calls that were active where the last uncaught runtime error was raised, outermost first. Errors
+    /// raised outside of any call have an empty trace.
+    pub fn stack_trace(&self) -> &[StackFrame<'t>] {
+        &self.error_trace
+    }
+
+    /// Prints every statement to stderr as it starts, and every expression with the value it evaluated to.
+    pub fn trace(mut self, enabled: bool) -> Self {
+        self.trace = enabled;
+        self
+    }
+
+    pub fn set_trace_hook(&mut self, hook: TraceHook<'a, 't>) {
+        self.trace_hook = Some(hook);
+    }
+
+    pub fn set_import_hook(&mut self, hook: ImportHook<'a, 't>) {
+        self.import_hook = Some(hook);
+    }
+
+    /// Makes the imports of the script being run relative to its directory, rather than to the working
+    /// directory, and keeps the script from importing itself.
+    pub fn set_script_path(&mut self, path: &Path) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.imported.insert(path.clone(), BoxedEnvironment::clone(&self.globals));
+        self.importing.push(path);
+    }
+
+    /// Defines a global alongside the natives, where scripts can shadow it and it doesn't count toward the
+    /// scope limit.
+    pub fn set_global(&mut self, name: impl Into<String>, value: Value<'a, 't>) -> Result<'a, 't, ()> {
+        self.builtins.borrow_mut().define(name, value)
+    }
+
+    /// The outermost scope, which top-level statements run in.
+    pub fn globals(&self) -> &BoxedEnvironment<'a, 't> {
+        &self.globals
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value<'a, 't>> {
+        self.globals.borrow().get(name)
+    }
+
+    /// The declarations that replaced a global since the last call, in the order they ran, so a REPL can point
+    /// out a line redefining what an earlier one declared.
+    pub fn take_redefined(&mut self) -> Vec<Token<'t>> {
+        mem::take(&mut self.redefined)
+    }
+
+    pub fn precision(mut self, significant_digits: Option<usize>) -> Self {
+        self.number_format.significant_digits = significant_digits;
+        self
+    }
+
+    /// Moves where printed numbers switch to scientific notation: from `from` in magnitude upward, and below
+    /// `below` toward zero. Either left out keeps its default, [`SCIENTIFIC_FROM`] or [`SCIENTIFIC_BELOW`].
+    pub fn scientific_notation(mut self, from: Option<f64>, below: Option<f64>) -> Self {
+        self.number_format.scientific_from = from.unwrap_or(SCIENTIFIC_FROM);
+        self.number_format.scientific_below = below.unwrap_or(SCIENTIFIC_BELOW);
+        self
+    }
+
+    /// Stops execution with an error once this many statements have run, so runaway loops terminate.
+    pub fn step_limit(mut self, limit: Option<usize>) -> Self {
+        self.step_limit = limit;
+        self
+    }
+
+    /// Fails defining a variable in a scope that already holds this many. The natives and the globals set with
+    /// [`TreeWalk::set_global`] don't count. Without a limit, scopes are still capped at [`DEFAULT_SCOPE_LIMIT`].
+    pub fn scope_limit(self, limit: Option<usize>) -> Self {
+        self.globals.borrow_mut().set_limit(limit.unwrap_or(DEFAULT_SCOPE_LIMIT));
+        self
+    }
 }
 
 impl Default for TreeWalk<'_, '_> {
@@ -39,7 +211,7 @@ impl Default for TreeWalk<'_, '_> {
 }
 
 impl<'a, 't> Evaluator<'a, 't> for TreeWalk<'a, 't> {
-    fn eval(&mut self, expr: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+    fn eval(&mut self, expr: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
         self.eval_expr(expr)
     }
 }
@@ -53,24 +225,214 @@ impl<'a, 't> Interpreter<'a, 't> for TreeWalk<'a, 't> {
     fn interpret_block(&mut self, block: &'a [Statement<'t>], env: BoxedEnvironment<'a, 't>) -> Result<'a, 't, ()> {
         self.eval_block_stmt(block, env)
     }
+
+    fn interpret_expr(&mut self, expr: &'a Expr<'t>, env: BoxedEnvironment<'a, 't>) -> Result<'a, 't, Value<'a, 't>> {
+        let old_env = mem::replace(&mut self.environment, env);
+        let value = self.eval_expr(expr);
+        self.environment = old_env;
+        value
+    }
+
+    fn call(&mut self, callee: &Value<'a, 't>, paren: &Token<'t>, args: Vec<Value<'a, 't>>) -> Result<'a, 't, Value<'a, 't>> {
+        check_call(callee, paren, args.len())?;
+        self.call_value(callee, paren, args)
+    }
+
+    fn display(&self, value: &Value) -> String {
+        value.formatted(&self.number_format).to_string()
+    }
 }
 
 impl<'a, 't> TreeWalk<'a, 't> {
+    /// Defines a declared name in the current scope, noting it when it replaces a global.
+    fn declare(&mut self, name: &Token<'t>, value: Value<'a, 't>) -> Result<'a, 't, ()> {
+        let replaced = self.environment.borrow_mut().define_checked(name.lexeme, value)?;
+        if replaced && Rc::ptr_eq(&self.environment, &self.globals) {
+            self.redefined.push(*name);
+        }
+        Ok(())
+    }
+
     fn eval_stmt(&mut self, statement: &'a Statement<'t>) -> Result<'a, 't, ()> {
-        match statement {
-            Statement::VarDecl(var_decl) => self.eval_var_decl(var_decl),
-            Statement::Print(print_statement) => self.eval_print_stmt(print_statement),
-            Statement::Block(block_statement) => self.eval_block_stmt(&block_statement.statements, Environment::boxed_with_enclosing(&self.environment)),
-            Statement::Expr(expression_statement) => self.eval_expr_stmt(expression_statement),
-            Statement::If(if_statement) => self.eval_if_stmt(if_statement),
-            Statement::While(while_statement) => self.eval_while_stmt(while_statement),
-            Statement::FunDecl(func_decl) => self.eval_fun_decl(func_decl),
-            Statement::Return(return_statement) => self.eval_return_stmt(return_statement),
-            Statement::ClassDecl(class_decl) => self.eval_class_decl(class_decl),
+        if let Some(limit) = self.step_limit {
+            if self.steps == limit {
+                return Err(RuntimeError::StepLimitExceeded { limit });
+            }
+            self.steps += 1;
+        }
+        if let Some(hook) = self.trace_hook.as_mut() {
+            hook(statement, &self.environment.borrow());
+        }
+        if self.trace {
+            // A statement is shown by its first formatted line, so a block or a function shows only its header
+            let source = formatter::format_program(slice::from_ref(statement));
+            let line = statement.line().map_or("?".to_string(), |line| line.to_string());
+            eprintln!("[line {line}] {}", source.lines().next().unwrap_or_default().trim());
         }
+        syntax::accept_stmt(self, statement)
+    }
+
+    fn eval_block_stmt(&mut self, stmts: &'a [Statement<'t>], env: BoxedEnvironment<'a, 't>) -> Result<'a, 't, ()> {
+        let old_env = BoxedEnvironment::clone(&self.environment);
+        self.environment = env;
+        for statement in stmts {
+            match self.eval_stmt(statement) {
+                Ok(()) => continue,
+                err @ Err(_) => {
+                    self.environment = old_env;
+                    return err;
+                },
+            }
+        }
+        self.environment = old_env;
+        Ok(())
     }
 
-    fn eval_class_decl(&mut self, stmt: &'a ClassDecl<'t>) -> Result<'a, 't, ()> {
+    /// Patterns are tried left to right and the rest are skipped once one matches.
+    fn case_matches(&mut self, subject: &Value<'a, 't>, patterns: &'a [CasePattern<'t>]) -> Result<'a, 't, bool> {
+        for pattern in patterns {
+            let matched = match pattern {
+                CasePattern::Value(value) => self.eval_expr(value)? == *subject,
+                CasePattern::Guard { operator, value } => {
+                    let value = self.eval_expr(value)?;
+                    is_true(&self.operate(subject.clone(), operator, value)?)
+                },
+            };
+            if matched {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn eval_catch(&mut self, catch: &'a CatchClause<'t>, error: RuntimeError<'a, 't>) -> Result<'a, 't, ()> {
+        let caught = match error {
+            RuntimeError::UserError { value, .. } => value,
+            error => Value::String(error.message().into()),
+        };
+        let env = Environment::boxed_with_enclosing(&self.environment);
+        env.borrow_mut().define(catch.name.lexeme, caught)?;
+        self.eval_block_stmt(&catch.block.statements, env)
+    }
+
+    /// Where names that aren't local to a function or block live: the namespace of the imported script
+    /// the running code comes from, or the globals.
+    fn global_scope(&self) -> BoxedEnvironment<'a, 't> {
+        let mut scope = BoxedEnvironment::clone(&self.environment);
+        loop {
+            let enclosing = match scope.borrow() {
+                env if env.is_namespace() || Rc::ptr_eq(&scope, &self.globals) => None,
+                env => env.enclosing(),
+            };
+            match enclosing {
+                Some(enclosing) => scope = enclosing,
+                None => return scope,
+            }
+        }
+    }
+
+    /// Runs one iteration of a loop and returns whether a `break` ended the loop. Jumps naming another
+    /// label keep unwinding to the loop they belong to.
+    fn eval_loop_body(&mut self, stmt: &'a WhileStatement<'t>) -> Result<'a, 't, bool> {
+        let targets_this_loop = |label: Option<&str>| label.is_none() || label == stmt.label.map(|label| label.lexeme);
+        match self.eval_stmt(&stmt.body) {
+            Err(RuntimeError::Break(label)) if targets_this_loop(label) => return Ok(true),
+            Err(RuntimeError::Continue(label)) if targets_this_loop(label) => {},
+            result => result?,
+        }
+        if let Some(increment) = &stmt.increment {
+            self.eval_expr(increment)?;
+        }
+        Ok(false)
+    }
+
+    fn eval_expr(&mut self, expr: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        let value = syntax::accept_expr(self, expr);
+        if let (true, Ok(value)) = (self.trace, &value) {
+            eprintln!("[line {}] {expr} => {}", expr.start().pos.line, self.display(value));
+        }
+        value
+    }
+
+    /// Indexing an instance calls its class's `get(key)` method, and assigning to an index calls `set(key, value)`.
+    fn call_index_method(
+        &mut self,
+        instance: &Rc<RefCell<Instance<'a, 't>>>,
+        name: &'static str,
+        bracket: &Token<'t>,
+        args: Vec<Value<'a, 't>>,
+    ) -> Result<'a, 't, Value<'a, 't>> {
+        let Some(method) = instance.borrow().method(name) else {
+            return Err(RuntimeError::MissingIndexMethod { token: *bracket, method: name });
+        };
+        if method.arity() != args.len() {
+            return Err(RuntimeError::InvalidArgumentCount {
+                token: *bracket,
+                callee: name.to_string(),
+                expected: method.arity(),
+                actual: args.len(),
+            });
+        }
+        method.bind(instance).call(self, args)
+    }
+
+    fn get_property(&mut self, object: Value<'a, 't>, name: &Token<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        match object {
+            Value::Instance(instance) => match Instance::get(&instance, name)? {
+                Value::Function(getter) if getter.is_getter() => getter.call(self, vec![]),
+                value => Ok(value),
+            },
+            Value::Module(module) => module.get(name),
+            _ => Err(RuntimeError::NotAnInstance { token: name.clone() }),
+        }
+    }
+
+    /// Calls a callee that [`check_call`] accepted with this many arguments.
+    fn call_value(&mut self, callee: &Value<'a, 't>, paren: &Token<'t>, args: Vec<Value<'a, 't>>) -> Result<'a, 't, Value<'a, 't>> {
+        let name = match callee {
+            Value::Function(func) => func.name(),
+            Value::NativeFunction(native) => native.name,
+            Value::Class(class) => class.name(),
+            _ => unreachable!(),
+        };
+        self.call_stack.push(StackFrame { name, line: paren.pos.line });
+        let result = match callee {
+            Value::Function(func) => func.call(self, args),
+            Value::NativeFunction(native) => native.call(self, paren, args),
+            Value::Class(class) => Class::init(class, self, args),
+            _ => unreachable!(),
+        };
+        // Only the innermost call an error leaves records the trace; the calls it unwinds through keep it
+        if result.is_err() && self.error_trace.is_empty() {
+            self.error_trace = self.call_stack.clone();
+        }
+        self.call_stack.pop();
+        result
+    }
+
+    /// Applies a binary operator, ordering an instance on the left through its class's `compare` method. An
+    /// instance without one, or only on the right, is an operand error like any other non-number.
+    fn operate(&mut self, left: Value<'a, 't>, operator: &Token<'t>, right: Value<'a, 't>) -> Result<'a, 't, Value<'a, 't>> {
+        use TokenType::*;
+        if let (Less | LessEq | Greater | GreaterEq, Value::Instance(instance)) = (operator.token_type, &left) {
+            if let Some(ordering) = Instance::compare(instance, self, operator, right.clone())? {
+                return Ok(Value::Bool(match operator.token_type {
+                    Less => ordering.is_lt(),
+                    LessEq => ordering.is_le(),
+                    Greater => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                }));
+            }
+        }
+        binary_operation(left, operator, right)
+    }
+}
+
+impl<'a, 't> ValueVisitor<'a, 't> for TreeWalk<'a, 't> {
+    type StmtOutput = Result<'a, 't, ()>;
+    type ExprOutput = Result<'a, 't, Value<'a, 't>>;
+
+    fn visit_class_decl(&mut self, stmt: &'a ClassDecl<'t>) -> Result<'a, 't, ()> {
         let name = stmt.name.lexeme;
 
         let superclass = match &stmt.superclass {
@@ -82,11 +444,12 @@ impl<'a, 't> TreeWalk<'a, 't> {
             Some(_) => unreachable!(),
         };
 
-        self.environment.borrow_mut().define(name, Value::Nil);
+        self.declare(&stmt.name, Value::Nil)?;
 
         if let Some(superclass) = &superclass {
-            self.environment = Environment::boxed_with_enclosing(&self.environment);
-            self.environment.borrow_mut().define("super".to_string(), Value::Class(superclass.clone()));
+            let env = Environment::boxed_with_enclosing(&self.environment);
+            env.borrow_mut().define("super".to_string(), Value::Class(superclass.clone()))?;
+            self.environment = env;
         }
 
         let methods = stmt
@@ -110,31 +473,45 @@ impl<'a, 't> TreeWalk<'a, 't> {
         Ok(())
     }
 
-    fn eval_var_decl(&mut self, stmt: &'a VariableDecl<'t>) -> Result<'a, 't, ()> {
-        let name = stmt.name.lexeme;
+    fn visit_var_decl(&mut self, stmt: &'a VariableDecl<'t>) -> Result<'a, 't, ()> {
         let value = match &stmt.initializer {
             Some(initializer) => self.eval_expr(initializer)?,
             None => Value::Nil,
         };
-        self.environment.borrow_mut().define(name, value);
+        self.declare(&stmt.name, value)
+    }
+
+    fn visit_destructure_decl(&mut self, stmt: &'a DestructureDecl<'t>) -> Result<'a, 't, ()> {
+        let Value::List(list) = self.eval_expr(&stmt.initializer)? else {
+            return Err(RuntimeError::NotDestructurable { token: stmt.bracket });
+        };
+        let values = list.borrow();
+        if values.len() != stmt.names.len() {
+            return Err(RuntimeError::DestructureMismatch {
+                token: stmt.bracket,
+                expected: stmt.names.len(),
+                actual: values.len(),
+            });
+        }
+        for (name, value) in stmt.names.iter().zip(values.iter()) {
+            self.declare(name, value.clone())?;
+        }
         Ok(())
     }
 
-    fn eval_fun_decl(&mut self, stmt: &'a FunctionDecl<'t>) -> Result<'a, 't, ()> {
+    fn visit_fun_decl(&mut self, stmt: &'a FunctionDecl<'t>) -> Result<'a, 't, ()> {
         let function = Function::new(stmt, BoxedEnvironment::clone(&self.environment), false);
-        self.environment
-            .borrow_mut()
-            .define(stmt.name.lexeme, Value::Function(Rc::new(function)));
-        Ok(())
+        self.declare(&stmt.name, Value::Function(Rc::new(function)))
     }
 
-    fn eval_print_stmt(&mut self, stmt: &PrintStatement<'t>) -> Result<'a, 't, ()> {
-        let value = self.eval_expr(&stmt.expr)?;
-        println!("{}", value);
+    fn visit_print(&mut self, stmt: &'a PrintStatement<'t>) -> Result<'a, 't, ()> {
+        let values = stmt.exprs.iter().map(|expr| self.eval_expr(expr)).collect::<Result<Vec<_>>>()?;
+        let output = values.iter().map(|value| self.display(value)).collect::<Vec<_>>().join(" ");
+        println!("{output}");
         Ok(())
     }
 
-    fn eval_return_stmt(&mut self, stmt: &ReturnStatement<'t>) -> Result<'a, 't, ()> {
+    fn visit_return(&mut self, stmt: &'a ReturnStatement<'t>) -> Result<'a, 't, ()> {
         let value = match &stmt.value {
             Some(value) => self.eval_expr(value)?,
             None => Value::Nil,
@@ -142,28 +519,122 @@ impl<'a, 't> TreeWalk<'a, 't> {
         Err(RuntimeError::Return(Some(value)))
     }
 
-    fn eval_block_stmt(&mut self, stmts: &'a [Statement<'t>], env: BoxedEnvironment<'a, 't>) -> Result<'a, 't, ()> {
-        let old_env = BoxedEnvironment::clone(&self.environment);
-        self.environment = env;
-        for statement in stmts {
-            match self.eval_stmt(statement) {
-                Ok(()) => continue,
-                err @ Err(_) => {
-                    self.environment = old_env;
-                    return err;
-                },
+    fn visit_switch(&mut self, stmt: &'a SwitchStatement<'t>) -> Result<'a, 't, ()> {
+        let subject = self.eval_expr(&stmt.subject)?;
+        for case in &stmt.cases {
+            if self.case_matches(&subject, &case.patterns)? {
+                return self.eval_block_stmt(&case.body, Environment::boxed_with_enclosing(&self.environment));
             }
         }
-        self.environment = old_env;
+        match &stmt.default {
+            Some(body) => self.eval_block_stmt(body, Environment::boxed_with_enclosing(&self.environment)),
+            None => Ok(()),
+        }
+    }
+
+    /// The `finally` block runs however the rest of the statement ended. Whatever leaves the `finally`
+    /// block early, be it an error, `return`, `break` or `continue`, replaces what was propagating, so a
+    /// `return` there overrides one from `try` or `catch`.
+    fn visit_try(&mut self, stmt: &'a TryStatement<'t>) -> Result<'a, 't, ()> {
+        let result = self.eval_block_stmt(&stmt.try_block.statements, Environment::boxed_with_enclosing(&self.environment));
+        let result = match (result, &stmt.catch) {
+            (Err(error), Some(catch)) if is_catchable(&error) => {
+                self.error_trace.clear();
+                self.eval_catch(catch, error)
+            },
+            (result, _) => result,
+        };
+        if let Some(finally_block) = &stmt.finally_block {
+            let pending_trace = mem::take(&mut self.error_trace);
+            self.eval_block_stmt(&finally_block.statements, Environment::boxed_with_enclosing(&self.environment))?;
+            self.error_trace = pending_trace;
+        }
+        result
+    }
+
+    /// The bound value is checked before the body runs, so a body never runs without a way to clean up
+    /// after it. An error leaving the body wins over one raised by `close`.
+    fn visit_using(&mut self, stmt: &'a UsingStatement<'t>) -> Result<'a, 't, ()> {
+        let value = self.eval_expr(&stmt.initializer)?;
+        let close = match &value {
+            Value::Instance(instance) => instance.borrow().method("close").map(|close| Value::Function(Rc::new(close.bind(instance)))),
+            _ => None,
+        };
+        let Some(close) = close else {
+            return Err(RuntimeError::NotClosable { keyword: stmt.keyword });
+        };
+        let env = Environment::boxed_with_enclosing(&self.environment);
+        env.borrow_mut().define(stmt.name.lexeme, value)?;
+        let result = self.eval_block_stmt(&stmt.body.statements, Environment::boxed_with_enclosing(&env));
+        let pending_trace = mem::take(&mut self.error_trace);
+        let closed = self.call(&close, &stmt.keyword, vec![]);
+        if result.is_err() {
+            self.error_trace = pending_trace;
+        }
+        result?;
+        closed.map(|_| ())
+    }
+
+    fn visit_throw(&mut self, stmt: &'a ThrowStatement<'t>) -> Result<'a, 't, ()> {
+        let value = self.eval(&stmt.value)?;
+        Err(RuntimeError::UserError { keyword: stmt.keyword, value })
+    }
+
+    /// Runs the imported script, unless it already ran, in the global scope or in a namespace of its own
+    /// bound to the `as` name. A script that is still running, because an import cycle leads back to it,
+    /// counts as already run, and importing a script again with `as` reaches the scope it first ran in.
+    /// A script must be imported the same way each time, since its declarations are either globals or
+    /// members of its namespace, never both.
+    fn visit_import(&mut self, stmt: &'a ImportStatement<'t>) -> Result<'a, 't, ()> {
+        let TokenLiteral::String(relative) = stmt.path.literal else {
+            unreachable!("import paths are string literals");
+        };
+        let failed = |message: String| RuntimeError::ImportFailed {
+            keyword: stmt.keyword,
+            message: format!("Could not import '{relative}': {message}"),
+        };
+        let directory = self.importing.last().and_then(|script| script.parent()).unwrap_or(Path::new(""));
+        let path = fs::canonicalize(directory.join(relative)).map_err(|e| failed(format!("{e}.")))?;
+        let scope = match self.imported.get(&path) {
+            Some(scope) => match (scope.borrow().is_namespace(), stmt.alias) {
+                (true, None) => return Err(failed("it was already imported with 'as'.".to_string())),
+                (false, Some(_)) => return Err(failed("it was already imported without 'as'.".to_string())),
+                _ => BoxedEnvironment::clone(scope),
+            },
+            None => {
+                let Some(hook) = &mut self.import_hook else {
+                    return Err(failed("imports are not supported here.".to_string()));
+                };
+                let statements = hook(&path).map_err(failed)?;
+                let scope = match stmt.alias {
+                    Some(_) => Environment::boxed_namespace(&self.globals),
+                    None => BoxedEnvironment::clone(&self.globals),
+                };
+                self.imported.insert(path.clone(), BoxedEnvironment::clone(&scope));
+                self.importing.push(path);
+                let result = self.eval_block_stmt(statements, BoxedEnvironment::clone(&scope));
+                self.importing.pop();
+                result?;
+                scope
+            },
+        };
+        if let Some(alias) = stmt.alias {
+            let module = Module::new(alias.lexeme, scope);
+            self.environment.borrow_mut().define(alias.lexeme, Value::Module(Rc::new(module)))?;
+        }
         Ok(())
     }
 
-    fn eval_expr_stmt(&mut self, stmt: &ExpressionStatement<'t>) -> Result<'a, 't, ()> {
+    fn visit_block(&mut self, stmt: &'a BlockStatement<'t>) -> Result<'a, 't, ()> {
+        self.eval_block_stmt(&stmt.statements, Environment::boxed_with_enclosing(&self.environment))
+    }
+
+    fn visit_expr_stmt(&mut self, stmt: &'a ExpressionStatement<'t>) -> Result<'a, 't, ()> {
         self.eval_expr(&stmt.expr)?;
         Ok(())
     }
 
-    fn eval_if_stmt(&mut self, stmt: &'a IfStatemnet<'t>) -> Result<'a, 't, ()> {
+    fn visit_if(&mut self, stmt: &'a IfStatemnet<'t>) -> Result<'a, 't, ()> {
         let condition_result = self.eval_expr(&stmt.condition)?;
         if is_true(&condition_result) {
             self.eval_stmt(&stmt.if_branch)?;
@@ -173,85 +644,215 @@ impl<'a, 't> TreeWalk<'a, 't> {
         Ok(())
     }
 
-    fn eval_while_stmt(&mut self, stmt: &'a WhileStatement<'t>) -> Result<'a, 't, ()> {
+    fn visit_while(&mut self, stmt: &'a WhileStatement<'t>) -> Result<'a, 't, ()> {
         while is_true(&self.eval_expr(&stmt.condition)?) {
-            self.eval_stmt(&stmt.body)?;
+            if self.eval_loop_body(stmt)? {
+                break;
+            }
         }
         Ok(())
     }
 
-    fn eval_expr(&mut self, expr: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
-        match expr {
-            Expr::Asign { name, value, height } => self.eval_assignment(name, value, height),
-            Expr::Binary { left, operator, right } => self.eval_binary(left, operator, right),
-            Expr::Unary { operator, expr } => self.eval_unary(operator, expr),
-            Expr::Grouping(expr) => self.eval_expr(expr),
-            Expr::Literal(literal) => Ok(Value::from(literal)),
-            Expr::Variable { name, height } => self.eval_variable(name, height),
-            Expr::LogicalOr { left, right } => self.eval_or(left, right),
-            Expr::LogicalAnd { left, right } => self.eval_and(left, right),
-            Expr::Call { callee, paren, args } => self.eval_call(callee, paren, args),
-            Expr::Get { object, name } => self.eval_get(object, name),
-            Expr::Set { object, name, value } => self.eval_set(object, name, value),
-            Expr::This { keyword, height } => self.eval_this(keyword, height),
-            Expr::Super { keyword, method, height } => self.eval_super(keyword, method, height),
+    fn visit_do_while(&mut self, stmt: &'a WhileStatement<'t>) -> Result<'a, 't, ()> {
+        loop {
+            if self.eval_loop_body(stmt)? || !is_true(&self.eval_expr(&stmt.condition)?) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn visit_break(&mut self, stmt: &'a LoopJump<'t>) -> Result<'a, 't, ()> {
+        Err(RuntimeError::Break(stmt.label.map(|label| label.lexeme)))
+    }
+
+    fn visit_continue(&mut self, stmt: &'a LoopJump<'t>) -> Result<'a, 't, ()> {
+        Err(RuntimeError::Continue(stmt.label.map(|label| label.lexeme)))
+    }
+
+    fn visit_grouping(&mut self, expr: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        self.eval_expr(expr)
+    }
+
+    fn visit_literal(&mut self, value: &'a Literal<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        Ok(Value::from(value))
+    }
+
+    fn visit_lambda(&mut self, decl: &'a FunctionDecl<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        Ok(Value::Function(Rc::new(Function::new(decl, BoxedEnvironment::clone(&self.environment), false))))
+    }
+
+    fn visit_list(&mut self, elements: &'a [Expr<'t>]) -> Result<'a, 't, Value<'a, 't>> {
+        let elements = elements.iter().map(|element| self.eval_expr(element)).collect::<Result<Vec<_>>>()?;
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_map(&mut self, entries: &'a [(Expr<'t>, Expr<'t>)]) -> Result<'a, 't, Value<'a, 't>> {
+        let mut map: Vec<(Value<'a, 't>, Value<'a, 't>)> = vec![];
+        for (key, value) in entries {
+            let key = self.eval_expr(key)?;
+            let value = self.eval_expr(value)?;
+            map_insert(&mut map, key, value);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    fn visit_interpolation(&mut self, segments: &[Cow<'t, str>], exprs: &'a [Expr<'t>]) -> Result<'a, 't, Value<'a, 't>> {
+        let mut string = segments[0].to_string();
+        for (expr, segment) in exprs.iter().zip(&segments[1..]) {
+            let value = self.eval_expr(expr)?;
+            string.push_str(&self.display(&value));
+            string.push_str(segment);
         }
+        Ok(Value::String(string.into()))
+    }
+
+    fn visit_index(&mut self, object: &'a Expr<'t>, bracket: &Token<'t>, index: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        let list = match self.eval_expr(object)? {
+            Value::List(list) => list,
+            Value::Instance(instance) => {
+                let index = self.eval_expr(index)?;
+                return self.call_index_method(&instance, "get", bracket, vec![index]);
+            },
+            // Looking up a missing key gives nil
+            Value::Map(map) => {
+                let key = self.eval_expr(index)?;
+                let value = map.borrow().iter().find(|(k, _)| *k == key).map(|(_, v)| v.clone());
+                return Ok(value.unwrap_or(Value::Nil));
+            },
+            _ => return Err(RuntimeError::NotIndexable { token: *bracket }),
+        };
+        let index = self.eval_expr(index)?;
+        let list = list.borrow();
+        let i = as_index(&index, list.len(), bracket)?;
+        Ok(list[i].clone())
+    }
+
+    fn visit_index_set(&mut self, object: &'a Expr<'t>, bracket: &Token<'t>, index: &'a Expr<'t>, value: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        let list = match self.eval_expr(object)? {
+            Value::List(list) => list,
+            Value::Instance(instance) => {
+                let index = self.eval_expr(index)?;
+                let value = self.eval_expr(value)?;
+                self.call_index_method(&instance, "set", bracket, vec![index, value.clone()])?;
+                return Ok(value);
+            },
+            Value::Map(map) => {
+                let key = self.eval_expr(index)?;
+                let value = self.eval_expr(value)?;
+                map_insert(&mut map.borrow_mut(), key, value.clone());
+                return Ok(value);
+            },
+            _ => return Err(RuntimeError::NotIndexable { token: *bracket }),
+        };
+        let index = self.eval_expr(index)?;
+        let value = self.eval_expr(value)?;
+        let mut list = list.borrow_mut();
+        let i = as_index(&index, list.len(), bracket)?;
+        list[i] = value.clone();
+        Ok(value)
+    }
+
+    fn visit_slice(
+        &mut self,
+        object: &'a Expr<'t>,
+        bracket: &Token<'t>,
+        start: Option<&'a Expr<'t>>,
+        end: Option<&'a Expr<'t>>,
+    ) -> Result<'a, 't, Value<'a, 't>> {
+        let Value::List(list) = self.eval_expr(object)? else {
+            return Err(RuntimeError::NotIndexable { token: *bracket });
+        };
+        let len = list.borrow().len();
+        let start = match start {
+            Some(expr) => slice_bound(&self.eval_expr(expr)?, len, bracket)?,
+            None => 0,
+        };
+        let end = match end {
+            Some(expr) => slice_bound(&self.eval_expr(expr)?, len, bracket)?,
+            None => len,
+        };
+        let elements = list.borrow().get(start..end.max(start)).unwrap_or_default().to_vec();
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
     }
 
-    fn eval_super(&mut self, keyword: &Token<'t>, method: &Token<'t>, height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_super(&mut self, keyword: &Token<'t>, method: &Token<'t>, height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
         let Some(Value::Class(superclass)) = self.lookup_var(keyword, height.get()) else {
-            panic!("Superclass not found");
+            return Err(RuntimeError::UnboundSuper { token: *keyword });
         };
-        let Value::Instance(object) = self.environment.borrow().get_at("this", height.get().unwrap() - 1).unwrap() else {
-            panic!("This is not found");
+        // `this` is bound in the scope just inside the one holding `super`
+        let this = height
+            .get()
+            .and_then(|h| h.checked_sub(1))
+            .and_then(|h| self.environment.borrow().get_at("this", h));
+        let Some(Value::Instance(object)) = this else {
+            return Err(RuntimeError::UnboundThis { token: *keyword });
         };
+        // Fields live on the instance rather than on a class, so `super` only ever resolves methods.
         let Some(method) = superclass.method(&method.lexeme) else {
-            return Err(RuntimeError::UndefinedProperty { token: method.clone() });
+            return Err(RuntimeError::UndefinedSuperMethod {
+                token: *method,
+                superclass: superclass.to_string(),
+            });
         };
         let method = method.bind(&object);
-        Ok(Value::Function(Rc::new(method)))
+        match method.is_getter() {
+            true => method.call(self, vec![]),
+            false => Ok(Value::Function(Rc::new(method))),
+        }
     }
 
-    fn eval_assignment(&mut self, name: &Token<'t>, value: &Box<Expr<'t>>, height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_assign(&mut self, name: &Token<'t>, value: &'a Expr<'t>, height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
         let value = self.eval_expr(value)?;
         match height.get() {
             Some(h) => self.environment.borrow_mut().assign_at(name.clone(), value.clone(), h),
-            None => self.globals.borrow_mut().assign(name.clone(), value.clone())?,
+            None => self.global_scope().borrow_mut().assign(name.clone(), value.clone())?,
         }
         Ok(value)
     }
 
-    fn eval_variable(&mut self, name: &Token<'t>, height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_variable(&mut self, name: &Token<'t>, height: &Cell<Option<usize>>, this_height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
+        if let Some(this_height) = this_height.get() {
+            let this = self.environment.borrow().get_at("this", this_height);
+            return match this {
+                Some(this) => self.get_property(this, name),
+                None => Err(RuntimeError::UnboundThis { token: *name }),
+            };
+        }
         match self.lookup_var(name, height.get()) {
             Some(value) => Ok(value.clone()),
             None => Err(RuntimeError::UndefinedVariable { token: name.clone() }),
         }
     }
 
-    fn eval_this(&mut self, keyword: &Token<'t>, height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_this(&mut self, keyword: &Token<'t>, height: &Cell<Option<usize>>) -> Result<'a, 't, Value<'a, 't>> {
         match self.lookup_var(keyword, height.get()) {
             Some(value) => Ok(value),
-            None => Err(RuntimeError::UndefinedVariable { token: keyword.clone() }),
+            None => Err(RuntimeError::UnboundThis { token: *keyword }),
         }
     }
 
-    fn eval_get(&mut self, object: &Expr<'t>, name: &Token<'t>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_get(&mut self, object: &'a Expr<'t>, name: &Token<'t>, optional: bool) -> Result<'a, 't, Value<'a, 't>> {
         match self.eval_expr(object)? {
-            Value::Instance(instance) => Instance::get(&instance, name),
-            _ => Err(RuntimeError::NotAnInstance { token: name.clone() }),
+            Value::Nil if optional => Ok(Value::Nil),
+            object => self.get_property(object, name),
         }
     }
 
-    fn eval_set(&mut self, object: &Expr<'t>, name: &Token<'t>, value: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
-        let Value::Instance(object) = self.eval_expr(object)? else {
+    fn visit_set(&mut self, object: &'a Expr<'t>, name: &Token<'t>, value: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+        let object = self.eval_expr(object)?;
+        if !matches!(object, Value::Instance(_) | Value::Module(_)) {
             return Err(RuntimeError::NotAnInstance { token: name.clone() });
-        };
+        }
         let value = self.eval_expr(value)?;
-        object.borrow_mut().set(&name.lexeme, value.clone());
+        match object {
+            Value::Module(module) => module.set(name, value.clone())?,
+            Value::Instance(instance) => instance.borrow_mut().set(&name.lexeme, value.clone()),
+            _ => unreachable!(),
+        }
         Ok(value)
     }
 
-    fn eval_or(&mut self, left: &Expr<'t>, right: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_logical_or(&mut self, left: &'a Expr<'t>, right: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
         let left_value = self.eval_expr(left)?;
         if is_true(&left_value) {
             return Ok(left_value);
@@ -260,7 +861,7 @@ impl<'a, 't> TreeWalk<'a, 't> {
         }
     }
 
-    fn eval_and(&mut self, left: &Expr<'t>, right: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_logical_and(&mut self, left: &'a Expr<'t>, right: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
         let left_value = self.eval_expr(left)?;
         if !is_true(&left_value) {
             return Ok(left_value);
@@ -269,73 +870,28 @@ impl<'a, 't> TreeWalk<'a, 't> {
         }
     }
 
-    fn eval_call(&mut self, callee: &Expr<'t>, paren: &Token<'t>, args: &[Expr<'t>]) -> Result<'a, 't, Value<'a, 't>> {
-        let callee = self.eval_expr(callee)?;
-        if !matches!(callee, Value::Function(_) | Value::NativeFunction(_) | Value::Class(_)) {
-            return Err(RuntimeError::NotValidCallable { token: paren.clone() });
-        }
-
-        let arg_len = match &callee {
-            Value::Function(func) => func.arity(),
-            Value::NativeFunction(func) => func.arity(),
-            Value::Class(class) => class.arity(),
-            _ => unreachable!(),
+    /// A method called through `?.` on `nil` isn't called: the call is `nil` and its arguments aren't
+    /// evaluated. Only a `nil` object is skipped, so calling a property that is `nil` still fails.
+    fn visit_call(&mut self, callee: &'a Expr<'t>, paren: &Token<'t>, args: &'a [Expr<'t>]) -> Result<'a, 't, Value<'a, 't>> {
+        let callee = match callee {
+            Expr::Get { object, name, optional: true } => match self.eval_expr(object)? {
+                Value::Nil => return Ok(Value::Nil),
+                object => self.get_property(object, name)?,
+            },
+            callee => self.eval_expr(callee)?,
         };
-
-        if args.len() != arg_len {
-            return Err(RuntimeError::InvalidArgumentCount {
-                token: paren.clone(),
-                expected: arg_len,
-                actual: args.len(),
-            });
-        }
-
+        check_call(&callee, paren, args.len())?;
         let args = args.iter().map(|arg| self.eval_expr(arg)).collect::<Result<Vec<_>>>()?;
-        match &callee {
-            Value::Function(func) => func.call(self, args),
-            Value::NativeFunction(native) => native.call(args),
-            Value::Class(class) => Class::init(&class, self, args),
-            _ => unreachable!(),
-        }
+        self.call_value(&callee, paren, args)
     }
 
-    fn eval_binary(&mut self, left: &Expr<'t>, operator: &Token<'t>, right: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_binary(&mut self, left: &'a Expr<'t>, operator: &Token<'t>, right: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
         let left_value = self.eval_expr(left)?;
         let right_value = self.eval_expr(right)?;
-        use TokenType::*;
-        match (left_value, operator.token_type, right_value) {
-            // Arithmetic operations
-            (Value::Number(l), Plus, Value::Number(r)) => Ok(Value::Number(l + r)),
-            (Value::Number(l), Minus, Value::Number(r)) => Ok(Value::Number(l - r)),
-            (Value::Number(l), Star, Value::Number(r)) => Ok(Value::Number(l * r)),
-            (Value::Number(l), Div, Value::Number(r)) => Ok(Value::Number(l / r)),
-            (Value::Number(l), Greater, Value::Number(r)) => Ok(Value::Bool(l > r)),
-            (Value::Number(l), GreaterEq, Value::Number(r)) => Ok(Value::Bool(l >= r)),
-            (Value::Number(l), Less, Value::Number(r)) => Ok(Value::Bool(l < r)),
-            (Value::Number(l), LessEq, Value::Number(r)) => Ok(Value::Bool(l <= r)),
-
-            // String operations
-            (Value::String(l), Plus, Value::String(r)) => Ok(Value::String(format!("{}{}", l, r))),
-
-            // Logical operations
-            (Value::Bool(l), And, Value::Bool(r)) => Ok(Value::Bool(l && r)),
-            (Value::Bool(l), Or, Value::Bool(r)) => Ok(Value::Bool(l || r)),
-
-            // Equality operations
-            (l, Equal, r) => Ok(Value::Bool(l == r)),
-            (l, NotEqual, r) => Ok(Value::Bool(l != r)),
-
-            // Incompatible types
-            (_, Plus | Minus | Div | Star | Greater | GreaterEq | Less | LessEq, _) => Err(RuntimeError::IncompatibleOperandType {
-                operator: operator.clone(),
-                message: "Operands must be numbers".to_string(),
-            }),
-
-            _ => panic!("Invalid binary operation"),
-        }
+        self.operate(left_value, operator, right_value)
     }
 
-    fn eval_unary(&mut self, operator: &Token<'t>, expr: &Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
+    fn visit_unary(&mut self, operator: &Token<'t>, expr: &'a Expr<'t>) -> Result<'a, 't, Value<'a, 't>> {
         let value = self.eval_expr(expr)?;
         match operator.token_type {
             TokenType::Minus => match value {
@@ -355,11 +911,150 @@ impl<'a, 't> TreeWalk<'a, 't> {
     fn lookup_var(&self, name: &Token<'t>, height: Option<usize>) -> Option<Value<'a, 't>> {
         match height {
             Some(h) => self.environment.borrow().get_at(&name.lexeme, h),
-            None => self.globals.borrow().get(&name.lexeme),
+            None => self.global_scope().borrow().get(&name.lexeme),
         }
     }
 }
 
+/// Control flow unwinding through a `try` isn't an error to catch, and catching the step budget or the
+/// scope limit would let a script keep running past it.
+fn is_catchable(error: &RuntimeError) -> bool {
+    !matches!(
+        error,
+        RuntimeError::Return(_)
+            | RuntimeError::Break(_)
+            | RuntimeError::Continue(_)
+            | RuntimeError::StepLimitExceeded { .. }
+            | RuntimeError::ScopeLimitExceeded { .. }
+    )
+}
+
+/// Checks that `callee` can be called and takes `arg_count` arguments.
+fn check_call<'a, 't>(callee: &Value<'a, 't>, paren: &Token<'t>, arg_count: usize) -> Result<'a, 't, ()> {
+    let (min_args, max_args, name) = match callee {
+        Value::Function(func) => (func.arity(), func.arity(), func.name()),
+        Value::NativeFunction(func) => (func.arity(), func.max_arity(), func.name),
+        Value::Class(class) => (class.arity(), class.arity(), class.name()),
+        _ => return Err(RuntimeError::NotValidCallable { token: *paren }),
+    };
+    if arg_count < min_args || arg_count > max_args {
+        return Err(RuntimeError::InvalidArgumentCount {
+            token: *paren,
+            callee: name.to_string(),
+            expected: if arg_count < min_args { min_args } else { max_args },
+            actual: arg_count,
+        });
+    }
+    Ok(())
+}
+
+fn binary_operation<'a, 't>(left: Value<'a, 't>, operator: &Token<'t>, right: Value<'a, 't>) -> Result<'a, 't, Value<'a, 't>> {
+    use TokenType::*;
+    match (left, operator.token_type, right) {
+        // Arithmetic operations
+        (Value::Number(l), Plus, Value::Number(r)) => Ok(Value::Number(l + r)),
+        (Value::Number(l), Minus, Value::Number(r)) => Ok(Value::Number(l - r)),
+        (Value::Number(l), Star, Value::Number(r)) => Ok(Value::Number(l * r)),
+        (Value::Number(l), Div, Value::Number(r)) => Ok(Value::Number(l / r)),
+        (Value::Number(_), FloorDiv, Value::Number(0.0)) => Err(RuntimeError::DivisionByZero { operator: *operator }),
+        (Value::Number(l), FloorDiv, Value::Number(r)) => Ok(Value::Number((l / r).floor())),
+        (Value::Number(l), StarStar, Value::Number(r)) => Ok(Value::Number(l.powf(r))),
+        (Value::Number(l), Greater, Value::Number(r)) => Ok(Value::Bool(l > r)),
+        (Value::Number(l), GreaterEq, Value::Number(r)) => Ok(Value::Bool(l >= r)),
+        (Value::Number(l), Less, Value::Number(r)) => Ok(Value::Bool(l < r)),
+        (Value::Number(l), LessEq, Value::Number(r)) => Ok(Value::Bool(l <= r)),
+
+        // String operations
+        (Value::String(l), Plus, Value::String(r)) => Ok(Value::String(format!("{}{}", l, r).into())),
+        (Value::String(s), Star, count @ Value::Number(_)) | (count @ Value::Number(_), Star, Value::String(s)) => match as_integer(&count) {
+            Some(n) if n >= 0 => match usize::try_from(n).ok().and_then(|n| s.len().checked_mul(n)) {
+                Some(len) if len <= MAX_STRING_LEN => Ok(Value::String(s.repeat(n as usize).into())),
+                _ => Err(repetition_too_long(operator)),
+            },
+            // Whole numbers past the range of `as_integer` are far too many repetitions too
+            None if matches!(count, Value::Number(n) if n.fract() == 0.0 && n > 0.0) => Err(repetition_too_long(operator)),
+            _ => Err(RuntimeError::IncompatibleOperandType {
+                operator: *operator,
+                message: "Repetition count must be a non-negative integer.".to_string(),
+            }),
+        },
+
+        // Logical operations
+        (Value::Bool(l), And, Value::Bool(r)) => Ok(Value::Bool(l && r)),
+        (Value::Bool(l), Or, Value::Bool(r)) => Ok(Value::Bool(l || r)),
+
+        // Membership: an element of a list, a key of a map or a substring of a string
+        (item, In, Value::List(list)) => Ok(Value::Bool(list.borrow().contains(&item))),
+        (key, In, Value::Map(map)) => Ok(Value::Bool(map.borrow().iter().any(|(k, _)| *k == key))),
+        (Value::String(needle), In, Value::String(haystack)) => Ok(Value::Bool(haystack.contains(&*needle))),
+        (_, In, Value::String(_)) => Err(RuntimeError::IncompatibleOperandType {
+            operator: *operator,
+            message: "Left operand of 'in' must be a string when the right one is.".to_string(),
+        }),
+        (_, In, _) => Err(RuntimeError::IncompatibleOperandType {
+            operator: *operator,
+            message: "Right operand of 'in' must be a list, a map or a string.".to_string(),
+        }),
+
+        // Equality operations
+        (l, Equal, r) => Ok(Value::Bool(l == r)),
+        (l, NotEqual, r) => Ok(Value::Bool(l != r)),
+
+        // Incompatible types
+        (_, Plus | Minus | Div | FloorDiv | Star | StarStar | Greater | GreaterEq | Less | LessEq, _) => Err(RuntimeError::IncompatibleOperandType {
+            operator: operator.clone(),
+            message: "Operands must be numbers".to_string(),
+        }),
+
+        _ => panic!("Invalid binary operation"),
+    }
+}
+
+/// Replaces the value of an existing key in place so maps keep their insertion order.
+fn map_insert<'a, 't>(map: &mut Vec<(Value<'a, 't>, Value<'a, 't>)>, key: Value<'a, 't>, value: Value<'a, 't>) {
+    match map.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, existing)) => *existing = value,
+        None => map.push((key, value)),
+    }
+}
+
+fn repetition_too_long<'a, 't>(operator: &Token<'t>) -> RuntimeError<'a, 't> {
+    RuntimeError::IncompatibleOperandType {
+        operator: *operator,
+        message: "Repeated string would be too long.".to_string(),
+    }
+}
+
+/// Reads a number with no fractional part as an integer; anything else, including integers too large
+/// for an `i64`, is rejected rather than truncated.
+fn as_integer(value: &Value) -> Option<i64> {
+    match value {
+        // `i64::MAX as f64` rounds up to 2^63, which is already out of range
+        Value::Number(n) if n.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(n) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+/// Resolves an element index, counting negative indices from the end of the list.
+fn as_index<'a, 't>(index: &Value<'a, 't>, len: usize, bracket: &Token<'t>) -> Result<'a, 't, usize> {
+    let Some(n) = as_integer(index) else {
+        return Err(RuntimeError::InvalidIndex { token: *bracket });
+    };
+    let i = if n < 0 { n + len as i64 } else { n };
+    if i < 0 || i >= len as i64 {
+        return Err(RuntimeError::IndexOutOfRange { token: *bracket });
+    }
+    Ok(i as usize)
+}
+
+fn slice_bound<'a, 't>(bound: &Value<'a, 't>, len: usize, bracket: &Token<'t>) -> Result<'a, 't, usize> {
+    let Some(n) = as_integer(bound) else {
+        return Err(RuntimeError::InvalidIndex { token: *bracket });
+    };
+    let i = if n < 0 { n + len as i64 } else { n };
+    Ok(i.clamp(0, len as i64) as usize)
+}
+
 const fn is_true(value: &Value) -> bool {
     match value {
         Value::Bool(b) => *b,
@@ -367,3 +1062,50 @@ const fn is_true(value: &Value) -> bool {
         _ => true,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, RecursiveDecendantParser};
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    fn parse(scanner: &Scanner) -> Vec<Statement<'_>> {
+        let statements = RecursiveDecendantParser::new().parse(scanner).unwrap();
+        let mut resolver = Resolver::new();
+        statements.iter().for_each(|stmt| resolver.resolve_stmt(stmt));
+        assert!(!resolver.has_err());
+        statements
+    }
+
+    #[test]
+    fn trace_hook_sees_each_statement_and_its_scope_before_it_runs() {
+        let scanner = Scanner::new(b"var x = 1;\n{\n    var x = 2;\n    x = x * 10;\n}\nx = 3;".to_vec());
+        let statements = parse(&scanner);
+        let seen = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = TreeWalk::new();
+        let log = Rc::clone(&seen);
+        interpreter.set_trace_hook(Box::new(move |stmt, env| {
+            log.borrow_mut().push((stmt.line().unwrap(), env.get("x").map(|x| x.to_string())));
+        }));
+        statements.iter().for_each(|stmt| interpreter.interpret(stmt).unwrap());
+
+        // A block reports the line of its first statement
+        let expected = [(1, None), (3, Some("1")), (3, Some("1")), (4, Some("2")), (6, Some("1"))];
+        assert_eq!(*seen.borrow(), expected.map(|(line, x)| (line, x.map(str::to_string))));
+    }
+
+    #[test]
+    fn host_globals_are_visible_to_the_script_and_its_results_to_the_host() {
+        let scanner = Scanner::new(b"var doubled = limit * 2;\nlimit = limit + 1;".to_vec());
+        let statements = parse(&scanner);
+        let mut interpreter = TreeWalk::new();
+        interpreter.set_global("limit", Value::Number(3.0)).unwrap();
+        statements.iter().for_each(|stmt| interpreter.interpret(stmt).unwrap());
+
+        assert!(matches!(interpreter.get_global("doubled"), Some(Value::Number(n)) if n == 6.0));
+        assert!(matches!(interpreter.get_global("limit"), Some(Value::Number(n)) if n == 4.0));
+        assert!(matches!(interpreter.get_global("clock"), Some(Value::NativeFunction(_))));
+        assert!(interpreter.get_global("missing").is_none());
+    }
+}
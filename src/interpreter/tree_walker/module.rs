@@ -0,0 +1,57 @@
+use std::fmt::Display;
+use std::rc::Rc;
+
+use super::env::BoxedEnvironment;
+use crate::interpreter::RuntimeError;
+use crate::syntax::Value;
+use crate::token::Token;
+
+/// A script imported with `as`. Its members are the declarations in the namespace its top-level code ran
+/// in, read and assigned like the fields of an instance, except that new ones can't be added.
+#[derive(Debug, Clone)]
+pub struct Module<'a, 't> {
+    name: &'t str,
+    namespace: BoxedEnvironment<'a, 't>,
+}
+
+impl<'a, 't> Module<'a, 't> {
+    pub fn new(name: &'t str, namespace: BoxedEnvironment<'a, 't>) -> Self {
+        Self { name, namespace }
+    }
+}
+
+impl<'a, 't> Module<'a, 't> {
+    pub fn get(&self, name: &Token<'t>) -> Result<Value<'a, 't>, RuntimeError<'a, 't>> {
+        match self.namespace.borrow().bindings().get(name.lexeme) {
+            Some(value) => Ok(value.clone()),
+            None => Err(RuntimeError::UndefinedProperty { token: *name }),
+        }
+    }
+
+    pub fn set(&self, name: &Token<'t>, value: Value<'a, 't>) -> Result<(), RuntimeError<'a, 't>> {
+        let mut namespace = self.namespace.borrow_mut();
+        if !namespace.bindings().contains_key(name.lexeme) {
+            return Err(RuntimeError::UndefinedProperty { token: *name });
+        }
+        namespace.define(name.lexeme, value)
+    }
+}
+
+impl Display for Module<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+/// Modules are the same when they share a namespace, which importing a script twice does.
+impl PartialEq for Module<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.namespace, &other.namespace)
+    }
+}
+
+impl PartialOrd for Module<'_, '_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.name.cmp(other.name))
+    }
+}
@@ -0,0 +1,14 @@
+#[macro_use]
+extern crate num_derive;
+
+pub mod formatter;
+pub mod interpreter;
+pub mod log;
+pub mod metrics;
+pub mod optimizer;
+pub mod parser;
+pub mod resolution;
+pub mod resolver;
+pub mod scanner;
+pub mod syntax;
+pub mod token;
@@ -1,6 +1,11 @@
+use crate::interpreter::tree_walker::StackFrame;
 use crate::interpreter::RuntimeError;
+use crate::parser::ParseError;
 use crate::token::{Token, TokenLiteral, TokenType};
 
+/// How many errors the parser and resolver each report before giving up on a script.
+pub const DEFAULT_MAX_ERRORS: usize = 20;
+
 pub fn error_unkown_symbol(line: u64, s: &str) {
     eprintln!("[line {line}] Error: Unexpected character: {s}");
 }
@@ -17,10 +22,29 @@ pub fn error_token(token: &Token, err: &str) {
     }
 }
 
+pub fn warning_token(token: &Token, warning: &str) {
+    eprintln!("[line {}] Warning at '{}': {warning}", token.pos.line, token.lexeme);
+}
+
+pub fn too_many_errors() {
+    eprintln!("Too many errors, stopping.");
+}
+
 pub fn error_runtime(err: &RuntimeError) {
     eprintln!("{err}")
 }
 
+/// Lists the calls an uncaught runtime error unwound through, innermost first.
+pub fn stack_trace(frames: &[StackFrame]) {
+    for frame in frames.iter().rev() {
+        eprintln!("in {}(), called from line {}", frame.name, frame.line);
+    }
+}
+
+pub fn error_parse(err: &ParseError) {
+    eprintln!("{err}")
+}
+
 pub fn token(token: &Token) {
     println!("{} {} {}", token_name(token), token.lexeme, token_value(token))
 }
@@ -41,13 +65,19 @@ pub const fn token_name(token: &Token) -> &'static str {
         RightParen => "RIGHT_PAREN",
         LeftBrace => "LEFT_BRACE",
         RightBrace => "RIGHT_BRACE",
+        LeftBracket => "LEFT_BRACKET",
+        RightBracket => "RIGHT_BRACKET",
+        Colon => "COLON",
         Plus => "PLUS",
         Minus => "MINUS",
         Dot => "DOT",
+        QuestionDot => "QUESTION_DOT",
         SemiColon => "SEMICOLON",
         Star => "STAR",
+        StarStar => "STAR_STAR",
         Comma => "COMMA",
         Asign => "EQUAL",
+        Arrow => "ARROW",
         Equal => "EQUAL_EQUAL",
         Eof => "EOF",
         Not => "BANG",
@@ -57,23 +87,39 @@ pub const fn token_name(token: &Token) -> &'static str {
         Greater => "GREATER",
         GreaterEq => "GREATER_EQUAL",
         Div => "SLASH",
+        FloorDiv => "BACKSLASH",
         String => "STRING",
+        Interpolation => "INTERPOLATION",
         Number => "NUMBER",
         Identifier => "IDENTIFIER",
         And => "AND",
+        As => "AS",
+        Break => "BREAK",
+        Catch => "CATCH",
+        Case => "CASE",
         Class => "CLASS",
+        Continue => "CONTINUE",
+        Default => "DEFAULT",
+        Do => "DO",
         Else => "ELSE",
         False => "FALSE",
+        Finally => "FINALLY",
         For => "FOR",
         Fun => "FUN",
         If => "IF",
+        Import => "IMPORT",
+        In => "IN",
         Nil => "NIL",
         Or => "OR",
         Print => "PRINT",
         Return => "RETURN",
         Super => "SUPER",
+        Switch => "SWITCH",
         This => "THIS",
+        Throw => "THROW",
         True => "TRUE",
+        Try => "TRY",
+        Using => "USING",
         Var => "VAR",
         While => "WHILE",
     }
@@ -1,51 +1,269 @@
-#[macro_use]
-extern crate num_derive;
-
+use std::cell::RefCell;
 use std::fs::File;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+use std::path::Path;
 use std::process::exit;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use codecrafters_interpreter::interpreter::{self, BoxedEnvironment, Environment, Evaluator, ImportHook, Interpreter};
+use codecrafters_interpreter::parser::{Parser, RecursiveDecendantParser};
+use codecrafters_interpreter::resolver::Resolver;
+use codecrafters_interpreter::scanner::Scanner;
+use codecrafters_interpreter::syntax::{Statement, Value};
+use codecrafters_interpreter::token::TokenType;
+use codecrafters_interpreter::{formatter, log, metrics, optimizer, resolution};
+
+#[derive(Default, Clone)]
+struct Options {
+    warn_shadowing: bool,
+    warn_no_effect: bool,
+    warn_super_init: bool,
+    implicit_methods: bool,
+    strict: bool,
+    optional_semicolons: bool,
+    precision: Option<usize>,
+    scientific_from: Option<f64>,
+    scientific_below: Option<f64>,
+    max_steps: Option<usize>,
+    max_scope_vars: Option<usize>,
+    max_errors: Option<usize>,
+    dump_resolved: bool,
+    fold_constants: bool,
+    print_last: bool,
+    time: bool,
+    trace: bool,
+}
+
+impl Options {
+    fn parse(flags: &[&str]) -> Result<Self, String> {
+        let mut options = Options::default();
+        for &flag in flags {
+            match flag.split_once('=') {
+                None if flag == "--warn-shadowing" => options.warn_shadowing = true,
+                None if flag == "--warn-no-effect" => options.warn_no_effect = true,
+                None if flag == "--warn-super-init" => options.warn_super_init = true,
+                None if flag == "--implicit-methods" => options.implicit_methods = true,
+                None if flag == "--strict" => options.strict = true,
+                None if flag == "--optional-semicolons" => options.optional_semicolons = true,
+                None if flag == "--dump-resolved" => options.dump_resolved = true,
+                None if flag == "--fold-constants" => options.fold_constants = true,
+                None if flag == "--print-last" => options.print_last = true,
+                None if flag == "--time" => options.time = true,
+                None if flag == "--trace" => options.trace = true,
+                Some(("--precision", digits)) => match digits.parse() {
+                    Ok(digits @ 1..) => options.precision = Some(digits),
+                    _ => return Err(format!("Invalid precision: {digits}")),
+                },
+                Some(("--scientific-from", threshold)) => options.scientific_from = Some(parse_threshold(threshold)?),
+                Some(("--scientific-below", threshold)) => options.scientific_below = Some(parse_threshold(threshold)?),
+                Some(("--max-steps", steps)) => match steps.parse() {
+                    Ok(steps) => options.max_steps = Some(steps),
+                    Err(_) => return Err(format!("Invalid step limit: {steps}")),
+                },
+                Some(("--max-scope-vars", vars)) => match vars.parse() {
+                    Ok(vars @ 1..) => options.max_scope_vars = Some(vars),
+                    _ => return Err(format!("Invalid scope limit: {vars}")),
+                },
+                Some(("--max-errors", errors)) => match errors.parse() {
+                    Ok(errors @ 1..) => options.max_errors = Some(errors),
+                    _ => return Err(format!("Invalid error limit: {errors}")),
+                },
+                _ => return Err(format!("Unknown option: {flag}")),
+            }
+        }
+        Ok(options)
+    }
+
+    fn parser<'t>(&self) -> RecursiveDecendantParser<'t> {
+        RecursiveDecendantParser::new()
+            .optional_semicolons(self.optional_semicolons)
+            .max_errors(self.max_errors())
+    }
+
+    fn resolver<'a>(&self) -> Resolver<'a> {
+        Resolver::new()
+            .warn_shadowing(self.warn_shadowing)
+            .warn_no_effect(self.warn_no_effect)
+            .warn_super_init(self.warn_super_init)
+            .implicit_methods(self.implicit_methods)
+            .strict(self.strict)
+            .max_errors(self.max_errors())
+    }
+
+    fn max_errors(&self) -> usize {
+        self.max_errors.unwrap_or(log::DEFAULT_MAX_ERRORS)
+    }
 
-use interpreter::{Evaluator, Interpreter};
-use parser::{Parser, RecursiveDecendantParser};
-use resolver::Resolver;
-use scanner::Scanner;
-use token::TokenType;
+    fn interpreter<'a, 't>(&self) -> interpreter::TreeWalk<'a, 't> {
+        let mut interpreter = interpreter::TreeWalk::new()
+            .precision(self.precision)
+            .scientific_notation(self.scientific_from, self.scientific_below)
+            .step_limit(self.max_steps)
+            .scope_limit(self.max_scope_vars)
+            .trace(self.trace);
+        interpreter.set_import_hook(self.import_hook());
+        interpreter
+    }
 
-mod interpreter;
-mod log;
-mod parser;
-mod resolver;
-mod scanner;
-mod syntax;
-mod token;
+    /// Prepares imported scripts with the same options as the script importing them. They live as long as
+    /// the interpreter, so like REPL lines their source and statements are leaked.
+    fn import_hook<'a, 't>(&self) -> ImportHook<'a, 't> {
+        let options = self.clone();
+        Box::new(move |path| {
+            let scanner = File::open(path).and_then(Scanner::try_from).map_err(|e| format!("{e}."))?;
+            let scanner: &'static Scanner = Box::leak(Box::new(scanner));
+            let statements = options.parser().parse(scanner);
+            let (false, Ok(mut statements)) = (scanner.has_error(), statements) else {
+                return Err("it has errors.".to_string());
+            };
+            if options.fold_constants {
+                optimizer::fold_constants(&mut statements);
+            }
+            let statements: &'static [Statement] = Box::leak(statements.into_boxed_slice());
+            let mut resolver = options.resolver();
+            statements.iter().for_each(|stmt| resolver.resolve_stmt(stmt));
+            if resolver.has_err() {
+                return Err("it has errors.".to_string());
+            }
+            Ok(statements)
+        })
+    }
+}
+
+/// A magnitude where numbers switch to scientific notation, which only makes sense as a positive number.
+fn parse_threshold(threshold: &str) -> Result<f64, String> {
+    match threshold.parse() {
+        Ok(magnitude) if magnitude > 0.0 && f64::is_finite(magnitude) => Ok(magnitude),
+        _ => Err(format!("Invalid scientific notation threshold: {threshold}")),
+    }
+}
 
 fn main() -> io::Result<()> {
     let args: Vec<String> = std::env::args().collect();
+    // Everything after a bare `--` is passed to the script, even when it looks like an option
+    let (args, passthrough) = match args.iter().position(|arg| arg == "--") {
+        Some(separator) => (&args[..separator], &args[separator + 1..]),
+        None => (&args[..], &[][..]),
+    };
+    let (flags, args): (Vec<&str>, Vec<&str>) = args.iter().map(String::as_str).partition(|arg| arg.starts_with("--"));
+    let options = match Options::parse(&flags) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}");
+            exit(64);
+        },
+    };
+    if args.get(1) == Some(&"repl") {
+        return repl(&options);
+    }
     if args.len() < 3 {
         writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
-        return Ok(());
+        exit(64);
     }
 
-    let command = &args[1];
-    let filename = &args[2];
+    let command = args[1];
+    let filename = args[2];
+    // Scripts see the arguments after the filename as the global `args`, so `args[0]` is the first of them
+    let script_args: Vec<&str> = args[3..].iter().copied().chain(passthrough.iter().map(String::as_str)).collect();
 
-    match command.as_str() {
+    match command {
         "tokenize" => tokenize(filename)?,
         "parse" => parse(filename)?,
-        "evaluate" => evaluate(filename)?,
-        "run" => run(filename)?,
+        "fmt" => format(filename, &options)?,
+        "check" => check(filename, &options)?,
+        "metrics" => print_metrics(filename, &options)?,
+        "evaluate" => evaluate(filename, &options)?,
+        "run" => run(filename, &script_args, &options)?,
         _ => {
             writeln!(io::stderr(), "Unknown command: {}", command).unwrap();
-            return Ok(());
+            exit(64);
         },
     }
 
     return Ok(());
 }
 
+/// Opens the script to run, reading it from stdin when the filename is `-`.
+fn open_scanner(filename: &str) -> io::Result<Scanner> {
+    if filename == "-" {
+        let mut source = Vec::new();
+        io::stdin().read_to_end(&mut source)?;
+        return Ok(Scanner::new(source));
+    }
+    Scanner::try_from(File::open(filename)?)
+}
+
+/// Runs each line read from stdin as a program, echoing the value of a trailing expression. Definitions
+/// carry over between lines, with a warning when one replaces a global, and an error only abandons the line
+/// it happened on.
+fn repl(options: &Options) -> io::Result<()> {
+    let parser = options.parser();
+    let mut interpreter = options.interpreter();
+    let mut line = String::new();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        line.clear();
+        if io::stdin().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        // Functions and classes keep referring to the code that declared them, so every line lives for the whole session
+        let scanner: &'static Scanner = Box::leak(Box::new(Scanner::new(line.clone().into_bytes())));
+        let Ok(statements) = parser.parse_with_trailing_expr(scanner) else {
+            continue;
+        };
+        if scanner.has_error() {
+            continue;
+        }
+        let statements: &'static [Statement] = Box::leak(statements.into_boxed_slice());
+        let mut resolver = options.resolver();
+        statements.iter().for_each(|stmt| resolver.resolve_stmt(stmt));
+        if resolver.has_err() {
+            continue;
+        }
+
+        let result = run_echoing(&mut interpreter, statements);
+        for name in interpreter.take_redefined() {
+            log::warning_token(&name, "Replaces the global of the same name.");
+        }
+        match result {
+            Ok(Some(value)) => println!("{}", interpreter.display(&value)),
+            Ok(None) => {},
+            Err(e) => {
+                log::error_runtime(&e);
+                log::stack_trace(interpreter.stack_trace());
+                interpreter.reset_scope();
+            },
+        }
+    }
+}
+
+/// Runs a REPL line and returns the value it echoes. A line echoes when its last statement is an expression,
+/// or a block whose own last statement is one, as in `{ var x = 2; x * x }`. That block is treated as an
+/// expression: its last expression is evaluated in the block's scope and its value echoed. Any other block,
+/// including one nested in another statement, runs as a statement and echoes nothing.
+fn run_echoing<'t>(interpreter: &mut interpreter::TreeWalk<'t, 't>, statements: &'t [Statement<'t>]) -> interpreter::Result<'t, 't, Option<Value<'t, 't>>> {
+    let Some((last, rest)) = statements.split_last() else {
+        return Ok(None);
+    };
+    rest.iter().try_for_each(|stmt| interpreter.interpret(stmt))?;
+    match last {
+        Statement::Expr(last) => interpreter.eval(&last.expr).map(Some),
+        Statement::Block(block) => match block.statements.split_last() {
+            Some((Statement::Expr(last), rest)) => {
+                let env = Environment::boxed_with_enclosing(interpreter.globals());
+                interpreter.interpret_block(rest, BoxedEnvironment::clone(&env))?;
+                interpreter.interpret_expr(&last.expr, env).map(Some)
+            },
+            _ => interpreter.interpret(last).map(|()| None),
+        },
+        _ => interpreter.interpret(last).map(|()| None),
+    }
+}
+
 fn tokenize(filename: &str) -> Result<(), io::Error> {
-    let file = File::open(filename)?;
-    let scanner = Scanner::try_from(file)?;
+    let scanner = open_scanner(filename)?;
     let mut tokens = vec![];
     loop {
         let token = scanner.next_token();
@@ -62,12 +280,11 @@ fn tokenize(filename: &str) -> Result<(), io::Error> {
 }
 
 fn parse(filename: &str) -> Result<(), io::Error> {
-    let file = File::open(filename)?;
-    let scanner = Scanner::try_from(file)?;
+    let scanner = open_scanner(filename)?;
     let parser = RecursiveDecendantParser::new();
 
     let expr = parser.parse_expr(&scanner);
-    if scanner.has_error() || expr.is_none() {
+    if scanner.has_error() || expr.is_err() {
         exit(65);
     }
 
@@ -75,51 +292,192 @@ fn parse(filename: &str) -> Result<(), io::Error> {
     Ok(())
 }
 
-fn evaluate(filename: &str) -> Result<(), io::Error> {
-    let file = File::open(filename)?;
-    let scanner = Scanner::try_from(file)?;
-    let parser = RecursiveDecendantParser::new();
+fn format(filename: &str, options: &Options) -> Result<(), io::Error> {
+    let scanner = open_scanner(filename)?;
+    let parser = options.parser();
 
-    let expr = parser.parse_expr(&scanner);
-    if scanner.has_error() || expr.is_none() {
+    let statements = parser.parse(&scanner);
+    if scanner.has_error() || statements.is_err() {
         exit(65);
     }
 
-    let mut interpreter = interpreter::TreeWalk::new();
-    let value = interpreter.eval(&expr.unwrap());
-    match value {
-        Ok(v) => println!("{}", v),
-        Err(e) => {
-            log::error_runtime(&e);
-            exit(70);
-        },
+    let mut statements = statements.unwrap();
+    if options.fold_constants {
+        optimizer::fold_constants(&mut statements);
     }
+    print!("{}", formatter::format_program(&statements));
+    Ok(())
+}
+
+fn print_metrics(filename: &str, options: &Options) -> Result<(), io::Error> {
+    let scanner = open_scanner(filename)?;
+    let parser = options.parser();
 
+    let statements = parser.parse(&scanner);
+    if scanner.has_error() || statements.is_err() {
+        exit(65);
+    }
+    println!("{}", metrics::program_metrics(&statements.unwrap()));
     Ok(())
 }
 
-fn run(filename: &str) -> Result<(), io::Error> {
-    let file = File::open(filename)?;
-    let scanner = Scanner::try_from(file)?;
-    let parser = RecursiveDecendantParser::new();
-    let mut resolver = Resolver::new();
-    let mut interpreter = interpreter::TreeWalk::new();
+/// Reports every scanner, parser and resolver diagnostic of a script without running it. Scripts it imports
+/// aren't checked.
+fn check(filename: &str, options: &Options) -> Result<(), io::Error> {
+    let scanner = open_scanner(filename)?;
+    let parser = options.parser();
+    let mut resolver = options.resolver();
 
     let statements = parser.parse(&scanner);
-    if scanner.has_error() || statements.is_none() {
+    if scanner.has_error() || statements.is_err() {
+        exit(65);
+    }
+    let statements = statements.unwrap();
+    statements.iter().for_each(|stmt| resolver.resolve_stmt(stmt));
+    if resolver.has_err() {
+        exit(65);
+    }
+    Ok(())
+}
+
+fn evaluate(filename: &str, options: &Options) -> Result<(), io::Error> {
+    let scanner = open_scanner(filename)?;
+    let parser = options.parser();
+    let mut resolver = options.resolver();
+
+    let statements = parser.parse_with_trailing_expr(&scanner);
+    if scanner.has_error() || statements.is_err() {
         exit(65);
     }
     let mut statements = statements.unwrap();
+    if options.fold_constants {
+        optimizer::fold_constants(&mut statements);
+    }
     statements.iter_mut().for_each(|stmt| resolver.resolve_stmt(stmt));
     if resolver.has_err() {
         exit(65);
     }
 
-    for stmt in &statements {
+    let mut interpreter = options.interpreter();
+    if filename != "-" {
+        interpreter.set_script_path(Path::new(filename));
+    }
+    let (last, rest) = match statements.split_last() {
+        Some((Statement::Expr(last), rest)) => (Some(&last.expr), rest),
+        _ => (None, &statements[..]),
+    };
+    for stmt in rest {
         if let Err(e) = interpreter.interpret(stmt) {
             log::error_runtime(&e);
+            log::stack_trace(interpreter.stack_trace());
             exit(70);
         }
     }
+    if let Some(expr) = last {
+        match interpreter.eval(expr) {
+            Ok(v) => println!("{}", interpreter.display(&v)),
+            Err(e) => {
+                log::error_runtime(&e);
+                log::stack_trace(interpreter.stack_trace());
+                exit(70);
+            },
+        }
+    }
+
     Ok(())
 }
+
+fn run(filename: &str, script_args: &[&str], options: &Options) -> Result<(), io::Error> {
+    let scanner = open_scanner(filename)?;
+    let parser = options.parser();
+    let mut resolver = options.resolver();
+
+    let mut stages = Stages::new(options.time);
+    let statements = parser.parse(&scanner);
+    if scanner.has_error() || statements.is_err() {
+        stages.exit(65);
+    }
+    let mut statements = statements.unwrap();
+    if options.fold_constants {
+        optimizer::fold_constants(&mut statements);
+    }
+    stages.finish("parse");
+    statements.iter_mut().for_each(|stmt| resolver.resolve_stmt(stmt));
+    if resolver.has_err() {
+        stages.exit(65);
+    }
+    stages.finish("resolve");
+    if options.dump_resolved {
+        resolution::resolution_report(&statements).iter().for_each(|line| println!("{line}"));
+        stages.report();
+        return Ok(());
+    }
+
+    let mut interpreter = options.interpreter();
+    if filename != "-" {
+        interpreter.set_script_path(Path::new(filename));
+    }
+    let script_args = script_args.iter().map(|&arg| Value::String(arg.into())).collect();
+    if let Err(e) = interpreter.set_global("args", Value::List(Rc::new(RefCell::new(script_args)))) {
+        log::error_runtime(&e);
+        stages.exit(70);
+    }
+    let (last, rest) = match statements.split_last() {
+        Some((Statement::Expr(last), rest)) if options.print_last => (Some(&last.expr), rest),
+        _ => (None, &statements[..]),
+    };
+    for stmt in rest {
+        if let Err(e) = interpreter.interpret(stmt) {
+            log::error_runtime(&e);
+            log::stack_trace(interpreter.stack_trace());
+            stages.exit(70);
+        }
+    }
+    if let Some(expr) = last {
+        match interpreter.eval(expr) {
+            Ok(v) => println!("{}", interpreter.display(&v)),
+            Err(e) => {
+                log::error_runtime(&e);
+                log::stack_trace(interpreter.stack_trace());
+                stages.exit(70);
+            },
+        }
+    }
+    stages.finish("execute");
+    stages.report();
+    Ok(())
+}
+
+/// The stages of a run that finished and how long each took, which `--time` reports even when a later stage
+/// fails. Each stage starts where the one before it finished.
+struct Stages {
+    enabled: bool,
+    started: Instant,
+    finished: Vec<(&'static str, Duration)>,
+}
+
+impl Stages {
+    fn new(enabled: bool) -> Self {
+        Self { enabled, started: Instant::now(), finished: vec![] }
+    }
+
+    fn finish(&mut self, stage: &'static str) {
+        let now = Instant::now();
+        self.finished.push((stage, now - self.started));
+        self.started = now;
+    }
+
+    fn report(&self) {
+        // Timings go to stderr so they never mix with the program's output
+        if self.enabled {
+            for (stage, duration) in &self.finished {
+                eprintln!("{:<8} {duration:?}", format!("{stage}:"));
+            }
+        }
+    }
+
+    fn exit(&self, code: i32) -> ! {
+        self.report();
+        exit(code)
+    }
+}
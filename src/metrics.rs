@@ -0,0 +1,97 @@
+use std::fmt::Display;
+
+use crate::syntax::*;
+
+/// Sizes of a parsed program, for spotting scripts that grew too large or too tangled.
+///
+/// Statements, expressions and methods each count as one node, and a top-level statement sits at depth 1.
+/// `for` loops are desugared by the parser, so they count as the block and `while` loop they become.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub nodes: usize,
+    pub max_depth: usize,
+    /// Function declarations, methods and lambdas.
+    pub functions: usize,
+    pub classes: usize,
+    /// Places where execution can take more than one path: conditions, loops, switch cases, catch
+    /// clauses and the short-circuiting `and` and `or`.
+    pub branches: usize,
+}
+
+pub fn program_metrics(statements: &[Statement]) -> Metrics {
+    let mut counter = Counter::default();
+    statements.iter().for_each(|stmt| counter.visit_stmt(stmt));
+    counter.metrics
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "nodes:     {}", self.nodes)?;
+        writeln!(f, "depth:     {}", self.max_depth)?;
+        writeln!(f, "functions: {}", self.functions)?;
+        writeln!(f, "classes:   {}", self.classes)?;
+        write!(f, "branches:  {}", self.branches)
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    metrics: Metrics,
+    depth: usize,
+}
+
+impl Visitor<'_> for Counter {
+    fn visit_stmt(&mut self, stmt: &Statement) {
+        self.enter();
+        match stmt {
+            Statement::If(_) | Statement::While(_) | Statement::DoWhile(_) => self.metrics.branches += 1,
+            Statement::Switch(stmt) => self.metrics.branches += stmt.cases.len(),
+            _ => {},
+        }
+        walk_stmt(self, stmt);
+        self.depth -= 1;
+    }
+
+    fn visit_fun_decl(&mut self, decl: &FunctionDecl) {
+        self.metrics.functions += 1;
+        walk_fun_decl(self, decl);
+    }
+
+    fn visit_class_decl(&mut self, decl: &ClassDecl) {
+        self.metrics.classes += 1;
+        if let Some(superclass) = &decl.superclass {
+            self.visit_expr(superclass);
+        }
+        for method in &decl.methods {
+            self.enter();
+            self.visit_fun_decl(method);
+            self.depth -= 1;
+        }
+    }
+
+    fn visit_catch(&mut self, catch: &CatchClause) {
+        self.metrics.branches += 1;
+        walk_catch(self, catch);
+    }
+
+    fn visit_lambda(&mut self, decl: &FunctionDecl) {
+        self.visit_fun_decl(decl);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.enter();
+        if let Expr::LogicalOr { .. } | Expr::LogicalAnd { .. } = expr {
+            self.metrics.branches += 1;
+        }
+        walk_expr(self, expr);
+        self.depth -= 1;
+    }
+}
+
+impl Counter {
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.metrics.nodes += 1;
+        self.metrics.max_depth = self.metrics.max_depth.max(self.depth);
+    }
+}
@@ -0,0 +1,88 @@
+use std::borrow::Cow;
+use std::mem;
+
+use crate::syntax::*;
+use crate::token::TokenType;
+
+/// Folds operators applied to literal operands into a single literal, in place.
+///
+/// Only folds whose result is exactly what evaluation would produce are applied: operations that
+/// fail at runtime (like `1 + "a"`) and non-finite results (like `1 / 0`) are left for the
+/// interpreter. Concatenated strings own their text, since it no longer appears in the source.
+pub fn fold_constants(statements: &mut [Statement]) {
+    statements.iter_mut().for_each(|stmt| ConstantFolder.visit_stmt(stmt));
+}
+
+struct ConstantFolder;
+
+impl VisitorMut for ConstantFolder {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+        if let Some(folded) = fold(expr) {
+            *expr = folded;
+        }
+    }
+}
+
+/// Computes the replacement for an expression whose operands have already been folded.
+fn fold<'t>(expr: &mut Expr<'t>) -> Option<Expr<'t>> {
+    use TokenType::*;
+    let literal = match expr {
+        Expr::Grouping { expr: inner, .. } => match inner.as_ref() {
+            Expr::Literal { value: literal, .. } => literal.clone(),
+            _ => return None,
+        },
+        Expr::Unary { operator, expr } => match (operator.token_type, expr.as_ref()) {
+            (Minus, Expr::Literal { value: Literal::Number(n), .. }) => Literal::Number(-n),
+            (Not, Expr::Literal { value: literal, .. }) => Literal::Bool(!is_truthy(literal)),
+            _ => return None,
+        },
+        Expr::Binary { left, operator, right } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Literal { value: l, .. }, Expr::Literal { value: r, .. }) => fold_binary(l, operator.token_type, r)?,
+            _ => return None,
+        },
+        Expr::LogicalOr { left, right, .. } => return fold_logical(left, right, true),
+        Expr::LogicalAnd { left, right, .. } => return fold_logical(left, right, false),
+        _ => return None,
+    };
+    Some(Expr::literal(*expr.start(), literal))
+}
+
+/// Logical operators yield one of their operands, so a literal left operand decides which one.
+fn fold_logical<'t>(left: &mut Expr<'t>, right: &mut Expr<'t>, is_or: bool) -> Option<Expr<'t>> {
+    let Expr::Literal { value: l, .. } = left else {
+        return None;
+    };
+    let kept = if is_truthy(l) == is_or { left } else { right };
+    let placeholder = Expr::literal(*kept.start(), Literal::Nil);
+    Some(mem::replace(kept, placeholder))
+}
+
+fn fold_binary<'t>(left: &Literal<'t>, operator: TokenType, right: &Literal<'t>) -> Option<Literal<'t>> {
+    use TokenType::*;
+    let folded = match (left, operator, right) {
+        (Literal::Number(l), Plus, Literal::Number(r)) => Literal::Number(l + r),
+        (Literal::String(l), Plus, Literal::String(r)) => Literal::String(Cow::Owned(format!("{l}{r}"))),
+        (Literal::Number(l), Minus, Literal::Number(r)) => Literal::Number(l - r),
+        (Literal::Number(l), Star, Literal::Number(r)) => Literal::Number(l * r),
+        (Literal::Number(l), Div, Literal::Number(r)) => Literal::Number(l / r),
+        (Literal::Number(l), FloorDiv, Literal::Number(r)) => Literal::Number((l / r).floor()),
+        (Literal::Number(l), StarStar, Literal::Number(r)) => Literal::Number(l.powf(*r)),
+        (Literal::Number(l), Greater, Literal::Number(r)) => Literal::Bool(l > r),
+        (Literal::Number(l), GreaterEq, Literal::Number(r)) => Literal::Bool(l >= r),
+        (Literal::Number(l), Less, Literal::Number(r)) => Literal::Bool(l < r),
+        (Literal::Number(l), LessEq, Literal::Number(r)) => Literal::Bool(l <= r),
+        (Literal::String(l), In, Literal::String(r)) => Literal::Bool(r.contains(l.as_ref())),
+        (l, Equal, r) => Literal::Bool(l == r),
+        (l, NotEqual, r) => Literal::Bool(l != r),
+        _ => return None,
+    };
+    match folded {
+        Literal::Number(n) if !n.is_finite() => None,
+        folded => Some(folded),
+    }
+}
+
+const fn is_truthy(literal: &Literal) -> bool {
+    !matches!(literal, Literal::Bool(false) | Literal::Nil)
+}
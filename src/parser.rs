@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::cell::{Cell, RefCell};
 
 use anyhow::Result;
@@ -9,23 +10,68 @@ use crate::scanner::Scanner;
 use crate::syntax::*;
 use crate::token::{Token, TokenLiteral, TokenType};
 
+/// Every method reports each error to stderr as it is found, and returns all of them if there were any.
 pub trait Parser<'t> {
-    fn parse(&self, scanner: &'t Scanner) -> Option<Vec<Statement<'t>>>;
-    fn parse_expr(&self, scanner: &'t Scanner) -> Option<Expr<'t>>;
+    fn parse(&self, scanner: &'t Scanner) -> Result<Vec<Statement<'t>>, Vec<ParseError<'t>>>;
+    /// Parses tokens that were scanned up front, so callers can inspect or rewrite them first.
+    fn parse_tokens(&self, tokens: Vec<Token<'t>>) -> Result<Vec<Statement<'t>>, Vec<ParseError<'t>>>;
+    fn parse_with_trailing_expr(&self, scanner: &'t Scanner) -> Result<Vec<Statement<'t>>, Vec<ParseError<'t>>>;
+    fn parse_expr(&self, scanner: &'t Scanner) -> Result<Expr<'t>, Vec<ParseError<'t>>>;
 }
 
 pub struct RecursiveDecendantParser<'t> {
     tokens: RefCell<Vec<Token<'t>>>,
     current: Cell<usize>,
-    has_error: Cell<bool>,
+    errors: RefCell<Vec<ParseError<'t>>>,
+    allow_trailing_expr: Cell<bool>,
+    optional_semicolons: bool,
+    max_errors: usize,
+    /// Whether an error past `max_errors` came up, after which parsing stops.
+    gave_up: Cell<bool>,
 }
 
-#[derive(Error, Debug)]
-enum ParseError {
-    #[error("Unexpected token")]
-    UnexpectedToken,
-    #[error("Expression error")]
-    ExpressionError,
+#[derive(Error, Debug, Clone)]
+pub enum ParseError<'t> {
+    #[error("[line {}] Error{}: {message}", token.pos.line, location(token))]
+    UnexpectedToken { token: Token<'t>, message: String },
+    #[error("[line {}] Error{}: {message}", token.pos.line, location(token))]
+    ExpressionError { token: Token<'t>, message: String },
+}
+
+impl<'t> ParseError<'t> {
+    fn unexpected(token: Token<'t>, message: impl Into<String>) -> Self {
+        ParseError::UnexpectedToken {
+            token,
+            message: message.into(),
+        }
+    }
+
+    fn expression(token: Token<'t>, message: impl Into<String>) -> Self {
+        ParseError::ExpressionError {
+            token,
+            message: message.into(),
+        }
+    }
+
+    /// The token the parser was looking at when it gave up.
+    pub fn token(&self) -> &Token<'t> {
+        match self {
+            ParseError::UnexpectedToken { token, .. } | ParseError::ExpressionError { token, .. } => token,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ParseError::UnexpectedToken { message, .. } | ParseError::ExpressionError { message, .. } => message,
+        }
+    }
+}
+
+fn location(token: &Token) -> String {
+    match token.token_type {
+        TokenType::Eof => " at end".to_string(),
+        _ => format!(" at '{}'", token.lexeme),
+    }
 }
 
 impl RecursiveDecendantParser<'_> {
@@ -33,9 +79,26 @@ impl RecursiveDecendantParser<'_> {
         Self {
             tokens: RefCell::new(vec![]),
             current: Cell::new(0),
-            has_error: Cell::new(false),
+            errors: RefCell::new(vec![]),
+            allow_trailing_expr: Cell::new(false),
+            optional_semicolons: false,
+            max_errors: log::DEFAULT_MAX_ERRORS,
+            gave_up: Cell::new(false),
         }
     }
+
+    /// Lets a line break end a statement in place of its `;`. A statement still continues onto the next
+    /// line when it isn't complete yet, as in a binary operation split after its operator.
+    pub fn optional_semicolons(mut self, enabled: bool) -> Self {
+        self.optional_semicolons = enabled;
+        self
+    }
+
+    /// Stops reporting errors, and parsing further declarations, when one more than this many comes up.
+    pub fn max_errors(mut self, max: usize) -> Self {
+        self.max_errors = max;
+        self
+    }
 }
 
 impl Default for RecursiveDecendantParser<'_> {
@@ -45,33 +108,54 @@ impl Default for RecursiveDecendantParser<'_> {
 }
 
 impl<'t> Parser<'t> for RecursiveDecendantParser<'t> {
-    fn parse(&self, scanner: &'t Scanner) -> Option<Vec<Statement<'t>>> {
-        *self.tokens.borrow_mut() = scanner.scan_all();
-        let statements = self.program();
-        if self.has_error.get() {
-            return None;
+    fn parse(&self, scanner: &'t Scanner) -> Result<Vec<Statement<'t>>, Vec<ParseError<'t>>> {
+        self.parse_tokens(scanner.scan_all())
+    }
+
+    fn parse_tokens(&self, mut tokens: Vec<Token<'t>>) -> Result<Vec<Statement<'t>>, Vec<ParseError<'t>>> {
+        // Parsing stops at `Eof`, so supply one if the caller's tokens don't end with it
+        if !matches!(tokens.last(), Some(Token { token_type: TokenType::Eof, .. })) {
+            let (line, offset) = tokens.last().map_or((1, 0), |token| (token.pos.end_line, token.pos.end_offset));
+            tokens.push(Token::eof(line, offset));
         }
-        Some(statements)
+        *self.tokens.borrow_mut() = tokens;
+        self.current.set(0);
+        self.errors.borrow_mut().clear();
+        self.gave_up.set(false);
+        let statements = self.program();
+        self.finish(statements)
+    }
+
+    fn parse_with_trailing_expr(&self, scanner: &'t Scanner) -> Result<Vec<Statement<'t>>, Vec<ParseError<'t>>> {
+        self.allow_trailing_expr.set(true);
+        let statements = self.parse(scanner);
+        self.allow_trailing_expr.set(false);
+        statements
     }
 
-    fn parse_expr(&self, scanner: &'t Scanner) -> Option<Expr<'t>> {
+    fn parse_expr(&self, scanner: &'t Scanner) -> Result<Expr<'t>, Vec<ParseError<'t>>> {
         *self.tokens.borrow_mut() = scanner.scan_all();
-        let expr = self.expression();
-        if expr.is_err() {
-            return None;
+        self.current.set(0);
+        self.errors.borrow_mut().clear();
+        self.gave_up.set(false);
+        match self.expression() {
+            Ok(expr) => self.finish(expr),
+            Err(error) => {
+                self.report(error);
+                Err(self.errors.take())
+            },
         }
-        Some(expr.unwrap())
     }
 }
 
 impl<'t> RecursiveDecendantParser<'t> {
     fn program(&self) -> Vec<Statement<'t>> {
         let mut statements: Vec<Statement<'t>> = vec![];
-        while self.peek().token_type != TokenType::Eof {
+        while self.peek().token_type != TokenType::Eof && !self.gave_up.get() {
             match self.declaration() {
                 Ok(stmt) => statements.push(stmt),
-                Err(_) => {
-                    self.has_error.set(true);
+                Err(error) => {
+                    self.report(error);
                     self.synchronize();
                 },
             }
@@ -79,17 +163,17 @@ impl<'t> RecursiveDecendantParser<'t> {
         statements
     }
 
-    fn declaration(&self) -> Result<Statement<'t>, ParseError> {
+    fn declaration(&self) -> Result<Statement<'t>, ParseError<'t>> {
         use TokenType::*;
         match self.peek().token_type {
-            Var => Ok(Statement::VarDecl(self.variable_declaration()?)),
+            Var => Ok(self.variable_declaration()?),
             Fun => Ok(Statement::FunDecl(self.function_declaration(FunctionType::Function)?)),
             Class => Ok(Statement::ClassDecl(self.class_declaration()?)),
             _ => Ok(self.statement()?),
         }
     }
 
-    fn class_declaration(&self) -> Result<ClassDecl<'t>, ParseError> {
+    fn class_declaration(&self) -> Result<ClassDecl<'t>, ParseError<'t>> {
         self.consume(TokenType::Class, "Expect 'class' before class name.")?;
         let name = self.consume(TokenType::Identifier, "Expect class name.")?;
         let superclass = if self.peek().token_type == TokenType::Less {
@@ -103,9 +187,14 @@ impl<'t> RecursiveDecendantParser<'t> {
         self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
         let mut methods = vec![];
         while !matches!(self.peek().token_type, TokenType::Eof | TokenType::RightBrace) {
-            match self.peek().token_type {
+            let token = self.peek();
+            match token.token_type {
                 TokenType::Identifier => methods.push(self.function_declaration(FunctionType::Method)?),
-                _ => {},
+                // Skip the stray member and keep collecting methods, so one typo doesn't derail the rest of the file
+                _ => {
+                    self.report(ParseError::unexpected(token, "Expect method declaration in class body."));
+                    self.skip_class_member();
+                },
             }
         }
         self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
@@ -113,8 +202,11 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(ClassDecl { name, methods, superclass })
     }
 
-    fn variable_declaration(&self) -> Result<VariableDecl<'t>, ParseError> {
+    fn variable_declaration(&self) -> Result<Statement<'t>, ParseError<'t>> {
         self.consume(TokenType::Var, "Expect 'var' before variable name.")?;
+        if self.peek().token_type == TokenType::LeftBracket {
+            return Ok(Statement::DestructureDecl(self.destructure_declaration()?));
+        }
         let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
         let initializer = match self.peek().token_type {
             TokenType::Asign => {
@@ -123,28 +215,79 @@ impl<'t> RecursiveDecendantParser<'t> {
             },
             _ => None,
         };
-        self.consume(TokenType::SemiColon, "Expect ';' after variable declaration.")?;
-        Ok(VariableDecl { name, initializer })
+        self.end_statement("Expect ';' after variable declaration.")?;
+        Ok(Statement::VarDecl(VariableDecl { name, initializer }))
+    }
+
+    fn destructure_declaration(&self) -> Result<DestructureDecl<'t>, ParseError<'t>> {
+        let bracket = self.consume(TokenType::LeftBracket, "Expect '[' before variable names.")?;
+        let mut names = vec![self.consume(TokenType::Identifier, "Expect variable name.")?];
+        while self.peek().token_type == TokenType::Comma {
+            self.advance();
+            names.push(self.consume(TokenType::Identifier, "Expect variable name.")?);
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after variable names.")?;
+        self.consume(TokenType::Asign, "Expect '=' after destructuring pattern.")?;
+        let initializer = self.expression()?;
+        self.end_statement("Expect ';' after variable declaration.")?;
+        Ok(DestructureDecl { bracket, names, initializer })
     }
 
-    fn function_declaration(&self, kind: FunctionType) -> Result<FunctionDecl<'t>, ParseError> {
+    fn function_declaration(&self, kind: FunctionType) -> Result<FunctionDecl<'t>, ParseError<'t>> {
         if matches!(kind, FunctionType::Function) {
             self.consume(TokenType::Fun, format!("Expect 'fun' before function name."))?;
         }
         let name = self.consume(TokenType::Identifier, format!("Expect '{kind}' name."))?;
+        if matches!(kind, FunctionType::Method) && self.peek().token_type == TokenType::LeftBrace {
+            return self.getter_declaration(name);
+        }
         self.consume(TokenType::LeftParen, format!("Expect '(' after {kind} name."))?;
         let params = self.parameters()?;
         self.consume(TokenType::RightParen, "message: Expect ')' after parameters.")?;
-        let body = self.block_statement(Some(kind))?.statements;
-        return Ok(FunctionDecl { name, params, body });
+        let body = match self.peek().token_type {
+            TokenType::Arrow => {
+                self.advance();
+                let expr = self.expression()?;
+                self.end_statement(format!("Expect ';' after {kind} body."))?;
+                FunctionBody::Expr(expr)
+            },
+            _ => FunctionBody::Block(self.block_statement(Some(kind))?.statements),
+        };
+        return Ok(FunctionDecl {
+            name,
+            params,
+            body,
+            is_getter: false,
+        });
     }
 
-    fn parameters(&self) -> Result<Vec<Token<'t>>, ParseError> {
+    /// A method declared without a parameter list is a getter, run whenever the property is read.
+    /// Its value is its trailing expression statement, which becomes an implicit return at the getter's name.
+    fn getter_declaration(&self, name: Token<'t>) -> Result<FunctionDecl<'t>, ParseError<'t>> {
+        let mut body = self.block_statement(Some(FunctionType::Method))?.statements;
+        if let Some(Statement::Expr(_)) = body.last() {
+            let Some(Statement::Expr(ExpressionStatement { expr })) = body.pop() else {
+                unreachable!()
+            };
+            body.push(Statement::Return(ReturnStatement {
+                return_token: name,
+                value: Some(expr),
+                implicit: true,
+            }));
+        }
+        Ok(FunctionDecl {
+            name,
+            params: vec![],
+            body: FunctionBody::Block(body),
+            is_getter: true,
+        })
+    }
+
+    fn parameters(&self) -> Result<Vec<Token<'t>>, ParseError<'t>> {
         let mut params = vec![];
         while self.peek().token_type != TokenType::RightParen {
             if params.len() >= 255 {
-                self.has_error.set(true);
-                log::error_token(&self.peek(), "Can't have more than 255 parameters.");
+                self.report(ParseError::unexpected(self.peek(), "Can't have more than 255 parameters."));
             }
             params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
             if self.peek().token_type != TokenType::Comma {
@@ -155,21 +298,33 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(params)
     }
 
-    fn statement(&self) -> Result<Statement<'t>, ParseError> {
+    fn statement(&self) -> Result<Statement<'t>, ParseError<'t>> {
         use TokenType::*;
         match self.peek().token_type {
             Print => Ok(Statement::Print(self.print_statement()?)),
+            // Only a labeled loop starts with a token followed by `:`, so otherwise this brace opens a map literal
+            LeftBrace if self.peek_n(2).token_type == Colon && !matches!(self.peek_n(3).token_type, While | For | Do) => {
+                Ok(Statement::Expr(self.expression_statement()?))
+            },
             LeftBrace => Ok(Statement::Block(self.block_statement(None)?)),
             If => Ok(Statement::If(self.if_statement()?)),
-            While => Ok(Statement::While(self.while_statement()?)),
-            For => Ok(self.desugar_for_statement()?),
+            While => Ok(Statement::While(self.while_statement(None)?)),
+            Do => Ok(Statement::DoWhile(self.do_while_statement(None)?)),
+            For => Ok(self.desugar_for_statement(None)?),
+            Identifier if self.peek_n(1).token_type == Colon => self.labeled_loop(),
             Return => Ok(Statement::Return(self.return_statement()?)),
+            Break => Ok(Statement::Break(self.loop_jump()?)),
+            Continue => Ok(Statement::Continue(self.loop_jump()?)),
+            Try => Ok(Statement::Try(self.try_statement()?)),
+            Using => Ok(Statement::Using(self.using_statement()?)),
+            Throw => Ok(Statement::Throw(self.throw_statement()?)),
+            Switch => Ok(Statement::Switch(self.switch_statement()?)),
+            Import => Ok(Statement::Import(self.import_statement()?)),
             _ => Ok(Statement::Expr(self.expression_statement()?)),
         }
     }
 
-    fn block_statement(&self, block_type: Option<FunctionType>) -> Result<BlockStatement<'t>, ParseError> {
-        let mut statements = vec![];
+    fn block_statement(&self, block_type: Option<FunctionType>) -> Result<BlockStatement<'t>, ParseError<'t>> {
         self.consume(
             TokenType::LeftBrace,
             match block_type {
@@ -177,15 +332,22 @@ impl<'t> RecursiveDecendantParser<'t> {
                 None => "Expect '{{' before block.".to_string(),
             },
         )?;
+        let statements = self.block_body()?;
+        Ok(BlockStatement { statements })
+    }
+
+    /// Parses the statements of a block whose opening brace was consumed, up to and including its closing brace.
+    fn block_body(&self) -> Result<Vec<Statement<'t>>, ParseError<'t>> {
+        let mut statements = vec![];
         while !matches!(self.peek().token_type, TokenType::RightBrace | TokenType::Eof) {
             let statement = self.declaration()?;
             statements.push(statement);
         }
         self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
-        Ok(BlockStatement { statements })
+        Ok(statements)
     }
 
-    fn if_statement(&self) -> Result<IfStatemnet<'t>, ParseError> {
+    fn if_statement(&self) -> Result<IfStatemnet<'t>, ParseError<'t>> {
         self.consume(TokenType::If, "Expect 'if' before condition.")?;
         self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
 
@@ -206,27 +368,212 @@ impl<'t> RecursiveDecendantParser<'t> {
         })
     }
 
-    fn while_statement(&self) -> Result<WhileStatement<'t>, ParseError> {
+    /// A label names the loop right after it, so `break label;` in a nested loop can leave it.
+    fn labeled_loop(&self) -> Result<Statement<'t>, ParseError<'t>> {
+        use TokenType::*;
+        let label = Some(self.advance());
+        self.consume(Colon, "Expect ':' after loop label.")?;
+        match self.peek().token_type {
+            While => Ok(Statement::While(self.while_statement(label)?)),
+            Do => Ok(Statement::DoWhile(self.do_while_statement(label)?)),
+            For => self.desugar_for_statement(label),
+            _ => Err(ParseError::unexpected(self.peek(), "Expect a loop after label.")),
+        }
+    }
+
+    fn while_statement(&self, label: Option<Token<'t>>) -> Result<WhileStatement<'t>, ParseError<'t>> {
         self.consume(TokenType::While, "Expect 'while' before condition.")?;
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
         let body = BoxedStatement::new(self.statement()?);
-        Ok(WhileStatement { condition, body })
+        Ok(WhileStatement {
+            label,
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    fn do_while_statement(&self, label: Option<Token<'t>>) -> Result<WhileStatement<'t>, ParseError<'t>> {
+        self.consume(TokenType::Do, "Expect 'do' before body.")?;
+        let body = BoxedStatement::new(self.statement()?);
+        self.consume(TokenType::While, "Expect 'while' after 'do' body.")?;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        self.end_statement("Expect ';' after do-while condition.")?;
+        Ok(WhileStatement {
+            label,
+            condition,
+            body,
+            increment: None,
+        })
+    }
+
+    fn try_statement(&self) -> Result<TryStatement<'t>, ParseError<'t>> {
+        self.consume(TokenType::Try, "Expect 'try' before block.")?;
+        let try_block = self.block_statement(None)?;
+        if !matches!(self.peek().token_type, TokenType::Catch | TokenType::Finally) {
+            return Err(ParseError::unexpected(self.peek(), "Expect 'catch' or 'finally' after try block."));
+        }
+        let catch = match self.peek().token_type {
+            TokenType::Catch => {
+                self.advance();
+                self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+                let name = self.consume(TokenType::Identifier, "Expect error variable name.")?;
+                self.consume(TokenType::RightParen, "Expect ')' after error variable.")?;
+                let block = self.block_statement(None)?;
+                Some(CatchClause { name, block })
+            },
+            _ => None,
+        };
+        let finally_block = match self.peek().token_type {
+            TokenType::Finally => {
+                self.advance();
+                Some(self.block_statement(None)?)
+            },
+            _ => None,
+        };
+        Ok(TryStatement {
+            try_block,
+            catch,
+            finally_block,
+        })
+    }
+
+    fn using_statement(&self) -> Result<UsingStatement<'t>, ParseError<'t>> {
+        let keyword = self.consume(TokenType::Using, "Expect 'using' before binding.")?;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'using'.")?;
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        self.consume(TokenType::Asign, "Expect '=' after variable name.")?;
+        let initializer = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after using binding.")?;
+        let body = self.block_statement(None)?;
+        Ok(UsingStatement {
+            keyword,
+            name,
+            initializer,
+            body,
+        })
+    }
+
+    /// Only the first matching case runs, with no fallthrough, and `default` runs when none matches.
+    fn switch_statement(&self) -> Result<SwitchStatement<'t>, ParseError<'t>> {
+        use TokenType::*;
+        let keyword = self.consume(Switch, "Expect 'switch' before subject.")?;
+        self.consume(LeftParen, "Expect '(' after 'switch'.")?;
+        let subject = self.expression()?;
+        self.consume(RightParen, "Expect ')' after switch subject.")?;
+        self.consume(LeftBrace, "Expect '{' before switch body.")?;
+        let mut cases = vec![];
+        let mut default = None;
+        loop {
+            let token = self.peek();
+            match token.token_type {
+                Case => {
+                    self.advance();
+                    let mut patterns = vec![self.case_pattern()?];
+                    while self.peek().token_type == Comma {
+                        self.advance();
+                        patterns.push(self.case_pattern()?);
+                    }
+                    self.consume(Colon, "Expect ':' after case patterns.")?;
+                    cases.push(SwitchCase {
+                        patterns,
+                        body: self.case_body()?,
+                    });
+                },
+                Default => {
+                    self.advance();
+                    self.consume(Colon, "Expect ':' after 'default'.")?;
+                    if default.is_some() {
+                        self.report(ParseError::unexpected(token, "Already a default case in this switch."));
+                    }
+                    default = Some(self.case_body()?);
+                },
+                _ => break,
+            }
+        }
+        self.consume(RightBrace, "Expect '}' after switch body.")?;
+        Ok(SwitchStatement {
+            keyword,
+            subject,
+            cases,
+            default,
+        })
+    }
+
+    /// A pattern starting with a comparison, equality or `in` operator is a guard whose operand is parsed above
+    /// comparisons, so `case > 1 + 2:` compares with `3`. Anything else is a value to compare against.
+    fn case_pattern(&self) -> Result<CasePattern<'t>, ParseError<'t>> {
+        use TokenType::*;
+        match self.peek().token_type {
+            Less | LessEq | Greater | GreaterEq | Equal | NotEqual | In => {
+                let operator = self.advance();
+                Ok(CasePattern::Guard { operator, value: self.term()? })
+            },
+            _ => Ok(CasePattern::Value(self.expression()?)),
+        }
+    }
+
+    fn case_body(&self) -> Result<Vec<Statement<'t>>, ParseError<'t>> {
+        use TokenType::*;
+        let mut statements = vec![];
+        while !matches!(self.peek().token_type, Case | Default | RightBrace | Eof) {
+            statements.push(self.declaration()?);
+        }
+        Ok(statements)
+    }
+
+    fn import_statement(&self) -> Result<ImportStatement<'t>, ParseError<'t>> {
+        let keyword = self.advance();
+        let path = self.consume(TokenType::String, "Expect path string after 'import'.")?;
+        let alias = match self.peek().token_type {
+            TokenType::As => {
+                self.advance();
+                Some(self.consume(TokenType::Identifier, "Expect module name after 'as'.")?)
+            },
+            _ => None,
+        };
+        self.end_statement("Expect ';' after import.")?;
+        Ok(ImportStatement { keyword, path, alias })
     }
 
-    fn return_statement(&self) -> Result<ReturnStatement<'t>, ParseError> {
+    fn throw_statement(&self) -> Result<ThrowStatement<'t>, ParseError<'t>> {
+        let keyword = self.advance();
+        let value = self.expression()?;
+        self.end_statement("Expect ';' after thrown value.")?;
+        Ok(ThrowStatement { keyword, value })
+    }
+
+    fn return_statement(&self) -> Result<ReturnStatement<'t>, ParseError<'t>> {
         let return_token = self.advance();
         let value = match self.peek().token_type {
             TokenType::SemiColon => None,
+            _ if self.at_line_break() => None,
             _ => Some(self.expression()?),
         };
-        self.consume(TokenType::SemiColon, "Expect ';' after return value.")?;
-        Ok(ReturnStatement { return_token, value })
+        self.end_statement("Expect ';' after return value.")?;
+        Ok(ReturnStatement {
+            return_token,
+            value,
+            implicit: false,
+        })
+    }
+
+    fn loop_jump(&self) -> Result<LoopJump<'t>, ParseError<'t>> {
+        let keyword = self.advance();
+        let label = match self.peek().token_type {
+            TokenType::Identifier if !self.at_line_break() => Some(self.advance()),
+            _ => None,
+        };
+        self.end_statement(format!("Expect ';' after '{}'.", keyword.lexeme))?;
+        Ok(LoopJump { keyword, label })
     }
 
-    fn desugar_for_statement(&self) -> Result<Statement<'t>, ParseError> {
-        self.consume(TokenType::For, "Expect 'for' before body.")?;
+    fn desugar_for_statement(&self, label: Option<Token<'t>>) -> Result<Statement<'t>, ParseError<'t>> {
+        let keyword = self.consume(TokenType::For, "Expect 'for' before body.")?;
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
         let initializer = match self.peek().token_type {
@@ -234,7 +581,7 @@ impl<'t> RecursiveDecendantParser<'t> {
                 self.advance();
                 None
             },
-            TokenType::Var => Some(Statement::VarDecl(self.variable_declaration()?)),
+            TokenType::Var => Some(self.variable_declaration()?),
             _ => Some(Statement::Expr(self.expression_statement()?)),
         };
         let condition = match self.peek().token_type {
@@ -248,23 +595,14 @@ impl<'t> RecursiveDecendantParser<'t> {
         };
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
-        let body = self.statement()?;
-        let body = match increment {
-            Some(expr) => Statement::Block(BlockStatement {
-                statements: vec![body, Statement::Expr(ExpressionStatement { expr })],
-            }),
-            None => body,
-        };
-        let body = match condition {
-            Some(expr) => Statement::While(WhileStatement {
-                condition: expr,
-                body: BoxedStatement::new(body),
-            }),
-            None => Statement::While(WhileStatement {
-                condition: Expr::literal(Literal::Bool(true)),
-                body: BoxedStatement::new(body),
-            }),
-        };
+        let body = BoxedStatement::new(self.statement()?);
+        let body = Statement::While(WhileStatement {
+            label,
+            // A missing condition is an always true literal placed at the `for`
+            condition: condition.unwrap_or_else(|| Expr::literal(keyword, Literal::Bool(true))),
+            body,
+            increment,
+        });
         let body = match initializer {
             Some(statement) => Statement::Block(BlockStatement {
                 statements: vec![statement, body],
@@ -274,64 +612,72 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(body)
     }
 
-    fn print_statement(&self) -> Result<PrintStatement<'t>, ParseError> {
+    fn print_statement(&self) -> Result<PrintStatement<'t>, ParseError<'t>> {
         let print_token = self.advance();
-        let expr = self.expression()?;
-        self.consume(TokenType::SemiColon, "Expect ';' after value.")?;
-        Ok(PrintStatement { print_token, expr })
+        let exprs = self.arguments()?;
+        self.end_statement("Expect ';' after value.")?;
+        Ok(PrintStatement { print_token, exprs })
     }
 
-    fn expression_statement(&self) -> Result<ExpressionStatement<'t>, ParseError> {
+    fn expression_statement(&self) -> Result<ExpressionStatement<'t>, ParseError<'t>> {
         let expr = self.expression()?;
-        self.consume(TokenType::SemiColon, "Expect ';' after expression.")?;
+        if self.allow_trailing_expr.get() && self.at_end_of_input() {
+            return Ok(ExpressionStatement { expr });
+        }
+        if expr.ends_with_lambda() && self.peek().token_type != TokenType::SemiColon {
+            return Ok(ExpressionStatement { expr });
+        }
+        self.end_statement("Expect ';' after expression.")?;
         Ok(ExpressionStatement { expr })
     }
 
-    fn expression(&self) -> Result<Expr<'t>, ParseError> {
+    fn expression(&self) -> Result<Expr<'t>, ParseError<'t>> {
         self.assignment()
     }
 
-    fn assignment(&self) -> Result<Expr<'t>, ParseError> {
+    fn assignment(&self) -> Result<Expr<'t>, ParseError<'t>> {
         let expr = self.logical_or()?;
         if self.peek().token_type == TokenType::Asign {
             let equals = self.advance();
             let value = self.assignment()?;
             match expr {
                 Expr::Variable { name, .. } => return Ok(Expr::assign(name.clone(), value)),
-                Expr::Get { name, object, .. } => return Ok(Expr::set(object, name, value)),
+                Expr::Get { name, object, optional: false } => return Ok(Expr::set(object, name, value)),
+                Expr::Index { object, bracket, index } => return Ok(Expr::index_set(object, bracket, index, value)),
+                Expr::This { .. } => self.report(ParseError::expression(equals, "Cannot assign to 'this'.")),
+                Expr::Super { method, .. } => self.report(ParseError::expression(equals, format!("Cannot assign to 'super.{}'.", method.lexeme))),
                 _ => {
-                    self.has_error.set(true);
-                    log::error_token(&equals, "Invalid assignment target.");
+                    self.report(ParseError::expression(equals, "Invalid assignment target."));
                 },
             }
         }
         Ok(expr)
     }
 
-    fn logical_or(&self) -> Result<Expr<'t>, ParseError> {
+    fn logical_or(&self) -> Result<Expr<'t>, ParseError<'t>> {
         let mut expr = self.logical_and()?;
         while let Token { token_type: TokenType::Or, .. } = self.peek() {
-            self.advance();
+            let operator = self.advance();
             let right = self.logical_and()?;
-            expr = Expr::or(expr, right);
+            expr = Expr::or(expr, operator, right);
         }
         Ok(expr)
     }
 
-    fn logical_and(&self) -> Result<Expr<'t>, ParseError> {
+    fn logical_and(&self) -> Result<Expr<'t>, ParseError<'t>> {
         let mut expr = self.equality()?;
         while let Token {
             token_type: TokenType::And, ..
         } = self.peek()
         {
-            self.advance();
+            let operator = self.advance();
             let right = self.equality()?;
-            expr = Expr::and(expr, right);
+            expr = Expr::and(expr, operator, right);
         }
         Ok(expr)
     }
 
-    fn equality(&self) -> Result<Expr<'t>, ParseError> {
+    fn equality(&self) -> Result<Expr<'t>, ParseError<'t>> {
         use TokenType::*;
         let mut expr = self.comparision()?;
         while let Token {
@@ -345,11 +691,12 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(expr)
     }
 
-    fn comparision(&self) -> Result<Expr<'t>, ParseError> {
+    /// `in` tests membership at the same level as the ordering operators, so `x + 1 in xs` adds first.
+    fn comparision(&self) -> Result<Expr<'t>, ParseError<'t>> {
         use TokenType::*;
         let mut expr = self.term()?;
         while let Token {
-            token_type: Greater | GreaterEq | Less | LessEq,
+            token_type: Greater | GreaterEq | Less | LessEq | In,
             ..
         } = self.peek()
         {
@@ -360,7 +707,7 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(expr)
     }
 
-    fn term(&self) -> Result<Expr<'t>, ParseError> {
+    fn term(&self) -> Result<Expr<'t>, ParseError<'t>> {
         use TokenType::*;
         let mut expr = self.factor()?;
         while let Token { token_type: Plus | Minus, .. } = self.peek() {
@@ -371,18 +718,28 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(expr)
     }
 
-    fn factor(&self) -> Result<Expr<'t>, ParseError> {
+    fn factor(&self) -> Result<Expr<'t>, ParseError<'t>> {
         use TokenType::*;
-        let mut expr = self.unary()?;
-        while let Token { token_type: Div | Star, .. } = self.peek() {
+        let mut expr = self.exponent()?;
+        while let Token { token_type: Div | FloorDiv | Star, .. } = self.peek() {
             let opr = self.advance();
-            let right = self.unary()?;
+            let right = self.exponent()?;
             expr = Expr::binary(expr, opr, right);
         }
         Ok(expr)
     }
 
-    fn unary(&self) -> Result<Expr<'t>, ParseError> {
+    fn exponent(&self) -> Result<Expr<'t>, ParseError<'t>> {
+        let expr = self.unary()?;
+        if self.peek().token_type == TokenType::StarStar {
+            let opr = self.advance();
+            let right = self.exponent()?;
+            return Ok(Expr::binary(expr, opr, right));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&self) -> Result<Expr<'t>, ParseError<'t>> {
         use TokenType::*;
         match self.peek() {
             Token { token_type: Not | Minus, .. } => {
@@ -394,26 +751,37 @@ impl<'t> RecursiveDecendantParser<'t> {
         }
     }
 
-    fn call(&self) -> Result<Expr<'t>, ParseError> {
+    fn call(&self) -> Result<Expr<'t>, ParseError<'t>> {
         let mut expr = self.primary()?;
-        while matches!(self.peek().token_type, TokenType::Dot | TokenType::LeftParen) {
+        while matches!(
+            self.peek().token_type,
+            TokenType::Dot | TokenType::QuestionDot | TokenType::LeftParen | TokenType::LeftBracket
+        ) {
             match self.advance().token_type {
                 TokenType::Dot => {
                     let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
                     expr = Expr::get(expr, name);
                 },
+                TokenType::QuestionDot => {
+                    let name = self.consume(TokenType::Identifier, "Expect property name after '?.'.")?;
+                    expr = Expr::optional_get(expr, name);
+                },
                 TokenType::LeftParen => {
-                    let args = match self.peek().token_type {
+                    let mut args = match self.peek().token_type {
                         TokenType::RightParen => vec![],
                         _ => self.arguments()?,
                     };
                     if args.len() >= 255 {
-                        self.has_error.set(true);
-                        log::error_token(&self.peek(), "Can't have more than 255 arguments.");
+                        self.report(ParseError::unexpected(self.peek(), "Can't have more than 255 arguments."));
                     }
                     let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+                    // A brace on the next line opens a block statement instead
+                    if self.peek().token_type == TokenType::LeftBrace && self.peek().pos.line == paren.pos.end_line {
+                        args.push(self.trailing_lambda()?);
+                    }
                     expr = Expr::call(expr, paren, args);
                 },
+                TokenType::LeftBracket => expr = self.subscript(expr)?,
                 _ => unreachable!(),
             }
         }
@@ -421,7 +789,38 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(expr)
     }
 
-    fn arguments(&self) -> Result<Vec<Expr<'t>>, ParseError> {
+    /// A block after a call's parentheses is a function passed as the last argument, so `repeat(3) { ... }`
+    /// calls `repeat` with 3 and the function. Parameters may lead the block, as in `{ a, b => ... }`.
+    fn trailing_lambda(&self) -> Result<Expr<'t>, ParseError<'t>> {
+        let brace = self.advance();
+        let mut params = vec![];
+        if self.peek().token_type == TokenType::Identifier && matches!(self.peek_n(1).token_type, TokenType::Comma | TokenType::Arrow) {
+            params = self.parameters()?;
+            self.consume(TokenType::Arrow, "Expect '=>' after lambda parameters.")?;
+        }
+        let body = self.block_body()?;
+        Ok(Expr::lambda(brace, params, body))
+    }
+
+    fn subscript(&self, object: Expr<'t>) -> Result<Expr<'t>, ParseError<'t>> {
+        let start = match self.peek().token_type {
+            TokenType::Colon => None,
+            _ => Some(self.expression()?),
+        };
+        if self.peek().token_type != TokenType::Colon {
+            let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+            return Ok(Expr::index(object, bracket, start.unwrap()));
+        }
+        self.advance();
+        let end = match self.peek().token_type {
+            TokenType::RightBracket => None,
+            _ => Some(self.expression()?),
+        };
+        let bracket = self.consume(TokenType::RightBracket, "Expect ']' after slice.")?;
+        Ok(Expr::slice(object, bracket, start, end))
+    }
+
+    fn arguments(&self) -> Result<Vec<Expr<'t>>, ParseError<'t>> {
         let expr = self.expression()?;
         let mut args = vec![expr];
         while let TokenType::Comma = self.peek().token_type {
@@ -432,26 +831,55 @@ impl<'t> RecursiveDecendantParser<'t> {
         Ok(args)
     }
 
-    fn primary(&self) -> Result<Expr<'t>, ParseError> {
+    fn primary(&self) -> Result<Expr<'t>, ParseError<'t>> {
         use TokenType::*;
         match self.advance() {
-            Token { token_type: Nil, .. } => Ok(Expr::literal(Literal::Nil)),
-            Token { token_type: True, .. } => Ok(Expr::literal(Literal::Bool(true))),
-            Token { token_type: False, .. } => Ok(Expr::literal(Literal::Bool(false))),
-            Token {
+            token @ Token { token_type: Nil, .. } => Ok(Expr::literal(token, Literal::Nil)),
+            token @ Token { token_type: True, .. } => Ok(Expr::literal(token, Literal::Bool(true))),
+            token @ Token { token_type: False, .. } => Ok(Expr::literal(token, Literal::Bool(false))),
+            token @ Token {
                 token_type: Number,
                 literal: TokenLiteral::Number(n),
                 ..
-            } => Ok(Expr::literal(Literal::Number(n))),
-            Token {
+            } => Ok(Expr::literal(token, Literal::Number(n))),
+            token @ Token {
                 token_type: String,
                 literal: TokenLiteral::String(s),
                 ..
-            } => Ok(Expr::literal(Literal::String(s))),
-            Token { token_type: LeftParen, .. } => {
+            } => Ok(Expr::literal(token, Literal::String(string_value(&token, s)))),
+            token @ Token {
+                token_type: Interpolation,
+                literal: TokenLiteral::String(s),
+                ..
+            } => self.interpolation(token, string_value(&token, s)),
+            paren @ Token { token_type: LeftParen, .. } => {
                 let expr = self.expression()?;
                 self.consume(RightParen, "Expect ')' after expression.")?;
-                Ok(Expr::grouping(expr))
+                Ok(Expr::grouping(paren, expr))
+            },
+            bracket @ Token { token_type: LeftBracket, .. } => {
+                let elements = match self.peek().token_type {
+                    RightBracket => vec![],
+                    _ => self.arguments()?,
+                };
+                self.consume(RightBracket, "Expect ']' after list elements.")?;
+                Ok(Expr::list(bracket, elements))
+            },
+            brace @ Token { token_type: LeftBrace, .. } => {
+                let mut entries = vec![];
+                if self.peek().token_type != RightBrace {
+                    loop {
+                        let key = self.expression()?;
+                        self.consume(Colon, "Expect ':' after map key.")?;
+                        entries.push((key, self.expression()?));
+                        if self.peek().token_type != Comma {
+                            break;
+                        }
+                        self.advance();
+                    }
+                }
+                self.consume(RightBrace, "Expect '}' after map entries.")?;
+                Ok(Expr::map(brace, entries))
             },
             keyword @ Token { token_type: This, .. } => Ok(Expr::this(keyword)),
             keyword @ Token { token_type: Super, .. } => {
@@ -460,21 +888,45 @@ impl<'t> RecursiveDecendantParser<'t> {
                 Ok(Expr::super_(keyword, method))
             },
             token @ Token { token_type: Identifier, .. } => Ok(Expr::variable(token.clone(), Cell::new(None))),
-            token => {
-                log::error_token(&token, "Expect expression.");
-                Err(ParseError::ExpressionError)
-            },
+            token => Err(ParseError::expression(token, "Expect expression.")),
+        }
+    }
+
+    /// Parses the expressions and segments following the first segment of an interpolated string. The
+    /// scanner ends each embedded expression with the next segment, and the last segment is a plain string.
+    fn interpolation(&self, start: Token<'t>, first: Cow<'t, str>) -> Result<Expr<'t>, ParseError<'t>> {
+        use TokenType::*;
+        let mut segments = vec![first];
+        let mut exprs = vec![];
+        loop {
+            exprs.push(self.expression()?);
+            let token = self.peek();
+            match token {
+                Token {
+                    token_type: token_type @ (Interpolation | String),
+                    literal: TokenLiteral::String(s),
+                    ..
+                } => {
+                    self.advance();
+                    segments.push(string_value(&token, s));
+                    if token_type == String {
+                        return Ok(Expr::interpolation(start, segments, exprs));
+                    }
+                },
+                _ => return Err(ParseError::expression(token, "Expect '}' after interpolated expression.")),
+            }
         }
     }
 }
 
 impl<'t> RecursiveDecendantParser<'t> {
     fn peek(&self) -> Token<'t> {
-        self.tokens
-            .borrow()
-            .get(self.current.get())
-            .cloned()
-            .unwrap_or_else(|| self.tokens.borrow().last().unwrap().clone())
+        self.peek_n(0)
+    }
+
+    /// Looks `n` tokens past the current one without consuming anything; past the end this is the `Eof` token.
+    fn peek_n(&self, n: usize) -> Token<'t> {
+        self.tokens.borrow().get(self.current.get() + n).copied().unwrap_or_else(|| self.end_token())
     }
 
     fn advance(&self) -> Token<'t> {
@@ -482,19 +934,83 @@ impl<'t> RecursiveDecendantParser<'t> {
         if token.is_some() {
             self.current.update(|c| c + 1);
         }
-        token.unwrap_or_else(|| self.tokens.borrow().last().unwrap().clone())
+        token.unwrap_or_else(|| self.end_token())
     }
 
-    fn consume(&self, tt: TokenType, message: impl Into<String>) -> Result<Token<'t>, ParseError> {
+    /// Reading past the end gives the stream's last token, `Eof`, or a fresh one if the stream is empty.
+    fn end_token(&self) -> Token<'t> {
+        self.tokens.borrow().last().copied().unwrap_or_else(|| Token::eof(1, 0))
+    }
+
+    fn consume(&self, tt: TokenType, message: impl Into<String>) -> Result<Token<'t>, ParseError<'t>> {
         match self.peek() {
             Token { token_type, .. } if token_type == tt => Ok(self.advance()),
-            token => {
-                log::error_token(&token, &message.into());
-                Err(ParseError::UnexpectedToken)
+            // A missing closing delimiter is reported but assumed present, so parsing carries on
+            token => match closing_lexeme(tt) {
+                Some(lexeme) => {
+                    self.report(ParseError::unexpected(token, message));
+                    Ok(Token::symbol(tt, lexeme, token.pos.line, token.pos.offset))
+                },
+                None => Err(ParseError::unexpected(token, message)),
             },
         }
     }
 
+    /// Consumes the `;` ending a statement, which may be left out before a line break when semicolons are optional.
+    fn end_statement(&self, message: impl Into<String>) -> Result<(), ParseError<'t>> {
+        if self.peek().token_type != TokenType::SemiColon && self.at_line_break() {
+            return Ok(());
+        }
+        self.consume(TokenType::SemiColon, message)?;
+        Ok(())
+    }
+
+    /// Whether semicolons are optional and the next token starts a new line, or there is none.
+    fn at_line_break(&self) -> bool {
+        let Some(previous) = self.current.get().checked_sub(1).and_then(|i| self.tokens.borrow().get(i).copied()) else {
+            return false;
+        };
+        let next = self.peek();
+        self.optional_semicolons && (next.token_type == TokenType::Eof || next.pos.line > previous.pos.end_line)
+    }
+
+    /// Whether only the end of the input is left, possibly after the braces closing the blocks around the
+    /// current statement. A trailing expression may leave out its `;` there, as in `{ 1; 2; 3 }`.
+    fn at_end_of_input(&self) -> bool {
+        let tokens = self.tokens.borrow();
+        tokens[self.current.get()..]
+            .iter()
+            .all(|token| matches!(token.token_type, TokenType::RightBrace | TokenType::Eof))
+    }
+
+    fn report(&self, error: ParseError<'t>) {
+        if self.errors.borrow().len() >= self.max_errors {
+            if !self.gave_up.replace(true) {
+                log::too_many_errors();
+            }
+            return;
+        }
+        log::error_parse(&error);
+        self.errors.borrow_mut().push(error);
+    }
+
+    fn finish<T>(&self, parsed: T) -> Result<T, Vec<ParseError<'t>>> {
+        let errors = self.errors.take();
+        match errors.is_empty() {
+            true => Ok(parsed),
+            false => Err(errors),
+        }
+    }
+
+    /// Skips at least one token, stopping after a `;` or before the `}` closing the class body.
+    fn skip_class_member(&self) {
+        while self.advance().token_type != TokenType::SemiColon {
+            if matches!(self.peek().token_type, TokenType::RightBrace | TokenType::Eof) {
+                return;
+            }
+        }
+    }
+
     fn synchronize(&self) {
         use TokenType::*;
         let mut token = self.advance();
@@ -503,7 +1019,7 @@ impl<'t> RecursiveDecendantParser<'t> {
                 return;
             }
             match self.peek().token_type {
-                Class | Fun | Var | For | If | While | Print | Return => return,
+                Class | Fun | Var | For | If | While | Do | Print | Return | Break | Continue | Try | Using | Throw | Switch | Import => return,
                 _ => {
                     token = self.advance();
                 },
@@ -511,3 +1027,173 @@ impl<'t> RecursiveDecendantParser<'t> {
         }
     }
 }
+
+const fn closing_lexeme(tt: TokenType) -> Option<&'static str> {
+    match tt {
+        TokenType::RightParen => Some(")"),
+        TokenType::RightBracket => Some("]"),
+        TokenType::RightBrace => Some("}"),
+        _ => None,
+    }
+}
+
+/// `\$` is the only escape in strings, there to keep a `$` from starting an interpolation. Raw strings have none.
+fn string_value<'t>(token: &Token<'t>, value: &'t str) -> Cow<'t, str> {
+    match !token.lexeme.starts_with('r') && value.contains("\\$") {
+        true => Cow::Owned(value.replace("\\$", "$")),
+        false => Cow::Borrowed(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_carry_the_offending_token_and_message() {
+        let scanner = Scanner::new(b"var = 1;\nprint (2;\nprint 3".to_vec());
+        let errors = RecursiveDecendantParser::new().parse(&scanner).unwrap_err();
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|error| (error.token().pos.line, error.token().lexeme, error.message()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (1, "=", "Expect variable name."),
+                (2, ";", "Expect ')' after expression."),
+                (3, "", "Expect ';' after value."),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_closing_delimiters_are_assumed_and_parsing_continues() {
+        let scanner = Scanner::new(b"print [1, f(2;\nfun g() { return (3 + ; }\nprint 4;".to_vec());
+        let errors = RecursiveDecendantParser::new().parse(&scanner).unwrap_err();
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|error| (error.token().pos.line, error.token().lexeme, error.message()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (1, ";", "Expect ')' after arguments."),
+                (1, ";", "Expect ']' after list elements."),
+                (2, ";", "Expect expression."),
+            ]
+        );
+
+        // The statement after the recovered one parses cleanly
+        let scanner = Scanner::new(b"var x = (1 + 2;\nprint x;".to_vec());
+        let errors = RecursiveDecendantParser::new().parse(&scanner).unwrap_err();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn pre_scanned_tokens_parse_after_being_rewritten() {
+        let scanner = Scanner::new(b"print a;\nprint b;".to_vec());
+        let mut tokens = scanner.scan_all();
+        // Drop `print b;` and its `Eof`, which parsing supplies again
+        tokens.truncate(3);
+        tokens.iter_mut().filter(|token| token.lexeme == "a").for_each(|token| token.lexeme = "c");
+        let statements = RecursiveDecendantParser::new().parse_tokens(tokens).unwrap();
+        assert_eq!(statements.len(), 1);
+        let Statement::Print(PrintStatement { exprs, .. }) = &statements[0] else {
+            panic!("expected a print statement");
+        };
+        assert!(matches!(&exprs[..], [Expr::Variable { name, .. }] if name.lexeme == "c"));
+
+        // The supplied `Eof` follows the last token, so errors at the end of input point there
+        let scanner = Scanner::new(b"print a;\nprint b;".to_vec());
+        let mut tokens = scanner.scan_all();
+        tokens.truncate(5);
+        let errors = RecursiveDecendantParser::new().parse_tokens(tokens).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].token().pos.line, errors[0].token().pos.offset), (2, 16));
+        assert_eq!(errors[0].message(), "Expect ';' after value.");
+    }
+
+    #[test]
+    fn parsing_stops_after_the_maximum_number_of_errors() {
+        let scanner = Scanner::new(b"var = 1;\nvar = 2;\nvar = 3;\nprint 4;".to_vec());
+        let errors = RecursiveDecendantParser::new().max_errors(2).parse(&scanner).unwrap_err();
+        let lines: Vec<_> = errors.iter().map(|error| error.token().pos.line).collect();
+        assert_eq!(lines, [1, 2]);
+    }
+
+    #[test]
+    fn assigning_to_this_or_super_has_its_own_error() {
+        let scanner = Scanner::new(b"this = 1;\nsuper.x = 2;\n(a) = 3;".to_vec());
+        let errors = RecursiveDecendantParser::new().parse(&scanner).unwrap_err();
+        let errors: Vec<_> = errors.iter().map(|error| (error.token().pos.line, error.message())).collect();
+        assert_eq!(
+            errors,
+            [
+                (1, "Cannot assign to 'this'."),
+                (2, "Cannot assign to 'super.x'."),
+                (3, "Invalid assignment target."),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_breaks_end_statements_only_when_semicolons_are_optional() {
+        let source = b"var a = 1\nreturn\nprint a +\n 2\nbreak\nlabel";
+        assert!(RecursiveDecendantParser::new().parse(&Scanner::new(source.to_vec())).is_err());
+        let scanner = Scanner::new(source.to_vec());
+        let statements = RecursiveDecendantParser::new().optional_semicolons(true).parse(&scanner).unwrap();
+        assert_eq!(statements.len(), 5);
+        assert!(matches!(&statements[1], Statement::Return(ReturnStatement { value: None, .. })));
+        assert!(matches!(&statements[3], Statement::Break(LoopJump { label: None, .. })));
+    }
+
+    #[test]
+    fn trailing_expressions_may_end_blocks_closed_at_the_end_of_input() {
+        let parse = |source: &[u8]| RecursiveDecendantParser::new().parse_with_trailing_expr(&Scanner::new(source.to_vec())).is_ok();
+        assert!(parse(b"{ 1; { 2 } }"));
+        assert!(!parse(b"{ 1 } 2;"));
+        assert!(RecursiveDecendantParser::new().parse(&Scanner::new(b"{ 1 }".to_vec())).is_err());
+    }
+
+    #[test]
+    fn trailing_lambdas_start_on_the_line_of_the_call() {
+        let scanner = Scanner::new(b"f(1) { a => print a; }\ng();\n{ print 2; }".to_vec());
+        let statements = RecursiveDecendantParser::new().parse(&scanner).unwrap();
+        assert_eq!(statements.len(), 3);
+        let Statement::Expr(ExpressionStatement { expr: Expr::Call { args, .. } }) = &statements[0] else {
+            panic!("expected a call");
+        };
+        assert!(matches!(&args[..], [_, Expr::Lambda { decl }] if decl.params.len() == 1));
+        assert!(matches!(&statements[2], Statement::Block(_)));
+    }
+
+    #[test]
+    fn errors_display_like_the_reported_diagnostics() {
+        let scanner = Scanner::new(b"1 +".to_vec());
+        let errors = RecursiveDecendantParser::new().parse_expr(&scanner).unwrap_err();
+        assert_eq!(errors[0].to_string(), "[line 1] Error at end: Expect expression.");
+        assert!(matches!(errors[0], ParseError::ExpressionError { .. }));
+    }
+
+    #[test]
+    fn expressions_start_at_their_first_token() {
+        let source = "(1)\n + [2]\n.len or {\n\"k\": nil} and \"${3}\"";
+        let scanner = Scanner::new(source.as_bytes().to_vec());
+        let expr = RecursiveDecendantParser::new().parse_expr(&scanner).unwrap();
+        let Expr::LogicalOr { left, right, .. } = &expr else {
+            panic!("expected 'or', got {expr}");
+        };
+        let Expr::LogicalAnd { left: map, right: string, .. } = right.as_ref() else {
+            panic!("expected 'and', got {right}");
+        };
+        let Expr::Binary { right: get, .. } = left.as_ref() else {
+            panic!("expected '+', got {left}");
+        };
+        let starts: Vec<_> = [&expr, get, map, string]
+            .iter()
+            .map(|expr| (expr.start().lexeme, expr.start().pos.line))
+            .collect();
+        assert_eq!(starts, [("(", 1), ("[", 2), ("{", 3), ("\"${", 4)]);
+    }
+}
@@ -0,0 +1,48 @@
+use std::cell::Cell;
+
+use crate::syntax::*;
+use crate::token::Token;
+
+/// Lists every variable reference in resolved statements together with the scope height the resolver
+/// assigned to it, one line per reference in source order.
+///
+/// References left unresolved are looked up in the globals at runtime and are reported as `global`.
+pub fn resolution_report(statements: &[Statement]) -> Vec<String> {
+    let mut report = Report::default();
+    statements.iter().for_each(|stmt| report.visit_stmt(stmt));
+    report.lines
+}
+
+#[derive(Default)]
+struct Report {
+    lines: Vec<String>,
+}
+
+impl Visitor<'_> for Report {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable { name, height, this_height } => match this_height.get() {
+                Some(this_height) => self
+                    .lines
+                    .push(format!("[line {}] {}: method on this at local {this_height}", name.pos.line, name.lexeme)),
+                None => self.reference(name, height),
+            },
+            Expr::Asign { name, height, value } => {
+                self.visit_expr(value);
+                self.reference(name, height);
+            },
+            Expr::This { keyword, height } | Expr::Super { keyword, height, .. } => self.reference(keyword, height),
+            expr => walk_expr(self, expr),
+        }
+    }
+}
+
+impl Report {
+    fn reference(&mut self, name: &Token, height: &Cell<Option<usize>>) {
+        let resolution = match height.get() {
+            Some(height) => format!("local {height}"),
+            None => "global".to_string(),
+        };
+        self.lines.push(format!("[line {}] {}: {resolution}", name.pos.line, name.lexeme));
+    }
+}
@@ -1,5 +1,5 @@
 use std::cell::Cell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::mem;
 
 use crate::log;
@@ -11,9 +11,11 @@ enum ScopeType {
     Function,
     Method,
     Initializer,
+    Getter,
     Normal,
 }
 
+#[derive(Clone, Copy)]
 enum ClassType {
     Class,
     Subclass,
@@ -24,7 +26,21 @@ pub struct Resolver<'a> {
     scopes: Vec<HashMap<&'a str, bool>>,
     current_scope: ScopeType,
     current_class: ClassType,
-    has_err: bool,
+    errors: usize,
+    max_errors: usize,
+    /// Whether an error past `max_errors` came up, after which resolution stops.
+    gave_up: bool,
+    warnings: usize,
+    warn_shadowing: bool,
+    warn_no_effect: bool,
+    warn_super_init: bool,
+    implicit_methods: bool,
+    strict: bool,
+    class_methods: HashSet<&'a str>,
+    /// Whether the initializer being resolved refers to `super.init`.
+    calls_super_init: bool,
+    /// The labels of the loops enclosing the statement being resolved, innermost last.
+    loops: Vec<Option<&'a str>>,
 }
 
 impl<'a> Resolver<'a> {
@@ -32,13 +48,68 @@ impl<'a> Resolver<'a> {
         Self {
             scopes: vec![],
             current_scope: ScopeType::Normal,
-            has_err: false,
+            errors: 0,
+            max_errors: log::DEFAULT_MAX_ERRORS,
+            gave_up: false,
+            warnings: 0,
             current_class: ClassType::None,
+            warn_shadowing: false,
+            warn_no_effect: false,
+            warn_super_init: false,
+            implicit_methods: false,
+            strict: false,
+            class_methods: HashSet::new(),
+            calls_super_init: false,
+            loops: vec![],
         }
     }
 
+    pub fn warn_shadowing(mut self, enabled: bool) -> Self {
+        self.warn_shadowing = enabled;
+        self
+    }
+
+    /// Warns about expression statements that compute a value without any side effect, like `a == b;`.
+    pub fn warn_no_effect(mut self, enabled: bool) -> Self {
+        self.warn_no_effect = enabled;
+        self
+    }
+
+    /// Warns about initializers of subclasses that never call `super.init()`, leaving the superclass part uninitialized.
+    pub fn warn_super_init(mut self, enabled: bool) -> Self {
+        self.warn_super_init = enabled;
+        self
+    }
+
+    /// Lets methods call the other methods of their class without `this.`, which standard Lox doesn't allow.
+    pub fn implicit_methods(mut self, enabled: bool) -> Self {
+        self.implicit_methods = enabled;
+        self
+    }
+
+    /// Fails resolution when any enabled warning was reported.
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    /// Stops reporting errors, and resolving further statements, when one more than this many comes up.
+    pub fn max_errors(mut self, max: usize) -> Self {
+        self.max_errors = max;
+        self
+    }
+
     pub fn has_err(&self) -> bool {
-        self.has_err
+        self.errors > 0 || (self.strict && self.warnings > 0)
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    /// Warnings are reported as they are found and only fail resolution in strict mode.
+    pub fn warning_count(&self) -> usize {
+        self.warnings
     }
 }
 
@@ -50,177 +121,292 @@ impl Default for Resolver<'_> {
 
 impl<'a> Resolver<'a> {
     pub fn resolve_stmt(&mut self, stmt: &'a Statement) {
-        match stmt {
-            Statement::VarDecl(var_decl) => self.resolve_var_decl(var_decl),
-            Statement::Print(print_statement) => self.resolve_print_stmt(print_statement),
-            Statement::Block(block_statement) => self.resolve_block_stmt(block_statement),
-            Statement::Expr(expression_statement) => self.resolve_expr_stmt(expression_statement),
-            Statement::If(if_statement) => self.resolve_if_stmt(if_statement),
-            Statement::While(while_statement) => self.resolve_while_stmt(while_statement),
-            Statement::FunDecl(func_decl) => self.resolve_fun_decl(func_decl),
-            Statement::Return(return_statement) => self.resolve_return_stmt(return_statement),
-            Statement::ClassDecl(class_decl) => self.resolve_class_decl(class_decl),
+        if self.gave_up {
+            return;
         }
+        self.visit_stmt(stmt);
+    }
+
+    pub fn resolve_expr(&mut self, expr: &'a Expr) {
+        self.visit_expr(expr);
     }
+}
 
-    fn resolve_class_decl(&mut self, stmt: &'a ClassDecl) {
-        let mut previos_class = ClassType::Class;
-        mem::swap(&mut self.current_class, &mut previos_class);
+impl<'a> Visitor<'a> for Resolver<'a> {
+    fn visit_class_decl(&mut self, stmt: &'a ClassDecl) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
         self.declare(&stmt.name);
         self.define(&stmt.name.lexeme);
 
         if let Some(super_expr @ Expr::Variable { name, .. }) = &stmt.superclass {
             if name.lexeme == stmt.name.lexeme {
-                self.has_err = true;
-                log::error_token(name, "A class can't inherit from itself.");
+                self.error(name, "A class can't inherit from itself.");
             }
             self.current_class = ClassType::Subclass;
-            self.resolve_expr(&super_expr);
+            self.visit_expr(&super_expr);
             self.begin_scope();
             self.scopes.last_mut().unwrap().insert("super", true);
         }
 
         self.begin_scope();
         self.scopes.last_mut().unwrap().insert("this", true);
-        for FunctionDecl { name, params, body } in &stmt.methods {
+        let enclosing_methods = mem::replace(&mut self.class_methods, stmt.methods.iter().map(|method| method.name.lexeme).collect());
+        let mut method_names = HashSet::new();
+        for FunctionDecl { name, params, body, is_getter } in &stmt.methods {
+            if !method_names.insert(name.lexeme) {
+                self.error(name, "Already a method with this name in this class.");
+            }
             let method_scope = match &name.lexeme[..] {
                 "init" => ScopeType::Initializer,
+                _ if *is_getter => ScopeType::Getter,
                 _ => ScopeType::Method,
             };
+            let enclosing_calls = mem::replace(&mut self.calls_super_init, false);
             self.resolve_function(params, body, method_scope);
+            let calls_super_init = mem::replace(&mut self.calls_super_init, enclosing_calls);
+            if self.warn_super_init && method_scope == ScopeType::Initializer && stmt.superclass.is_some() && !calls_super_init {
+                self.warning(name, "Subclass initializer doesn't call 'super.init()'.");
+            }
         }
 
+        self.class_methods = enclosing_methods;
         self.end_scope();
         if stmt.superclass.is_some() {
             self.end_scope();
         }
-        self.current_class = previos_class;
+        self.current_class = enclosing_class;
     }
 
-    fn resolve_var_decl(&mut self, stmt: &'a VariableDecl) {
+    fn visit_var_decl(&mut self, stmt: &'a VariableDecl) {
         self.declare(&stmt.name);
-        if let Some(initializer) = &stmt.initializer {
-            self.resolve_expr(initializer);
-        }
+        walk_var_decl(self, stmt);
         self.define(&stmt.name.lexeme);
     }
 
-    fn resolve_block_stmt(&mut self, stmt: &'a BlockStatement) {
+    fn visit_destructure_decl(&mut self, stmt: &'a DestructureDecl) {
+        stmt.names.iter().for_each(|name| self.declare(name));
+        self.visit_expr(&stmt.initializer);
+        stmt.names.iter().for_each(|name| self.define(name.lexeme));
+    }
+
+    fn visit_block(&mut self, stmt: &'a BlockStatement) {
         self.begin_scope();
-        for statement in &stmt.statements {
-            self.resolve_stmt(statement);
-        }
+        self.resolve_statements(&stmt.statements);
         self.end_scope();
     }
 
-    fn resolve_print_stmt(&mut self, stmt: &'a PrintStatement) {
-        self.resolve_expr(&stmt.expr);
+    fn visit_switch(&mut self, stmt: &'a SwitchStatement) {
+        self.visit_expr(&stmt.subject);
+        for case in &stmt.cases {
+            for pattern in &case.patterns {
+                match pattern {
+                    CasePattern::Value(value) | CasePattern::Guard { value, .. } => self.visit_expr(value),
+                }
+            }
+            self.resolve_case_body(&case.body);
+        }
+        if let Some(body) = &stmt.default {
+            self.resolve_case_body(body);
+        }
+    }
+
+    fn visit_expr_stmt(&mut self, stmt: &'a ExpressionStatement) {
+        if self.warn_no_effect && !has_effect(&stmt.expr) {
+            self.warning(leading_token(&stmt.expr), "Expression statement has no effect.");
+        }
+        self.visit_expr(&stmt.expr);
     }
 
-    fn resolve_expr_stmt(&mut self, stmt: &'a ExpressionStatement) {
-        self.resolve_expr(&stmt.expr);
+    fn visit_while(&mut self, stmt: &'a WhileStatement) {
+        self.loops.push(stmt.label.map(|label| label.lexeme));
+        walk_while(self, stmt);
+        self.loops.pop();
     }
 
-    fn resolve_if_stmt(&mut self, stmt: &'a IfStatemnet) {
-        self.resolve_expr(&stmt.condition);
-        self.resolve_stmt(&stmt.if_branch);
-        if let Some(else_branch) = &stmt.else_branch {
-            self.resolve_stmt(else_branch);
-        }
+    fn visit_do_while(&mut self, stmt: &'a WhileStatement) {
+        self.loops.push(stmt.label.map(|label| label.lexeme));
+        walk_do_while(self, stmt);
+        self.loops.pop();
     }
 
-    fn resolve_while_stmt(&mut self, stmt: &'a WhileStatement) {
-        self.resolve_expr(&stmt.condition);
-        self.resolve_stmt(&stmt.body);
+    fn visit_break(&mut self, stmt: &'a LoopJump) {
+        self.resolve_loop_jump(stmt);
     }
 
-    fn resolve_fun_decl(&mut self, stmt: &'a FunctionDecl) {
+    fn visit_continue(&mut self, stmt: &'a LoopJump) {
+        self.resolve_loop_jump(stmt);
+    }
+
+    fn visit_catch(&mut self, catch: &'a CatchClause) {
+        self.begin_scope();
+        self.declare(&catch.name);
+        self.define(catch.name.lexeme);
+        self.resolve_statements(&catch.block.statements);
+        self.end_scope();
+    }
+
+    /// The initializer is resolved outside the binding's scope, and the body is a block within it.
+    fn visit_using(&mut self, stmt: &'a UsingStatement) {
+        self.visit_expr(&stmt.initializer);
+        self.begin_scope();
         self.declare(&stmt.name);
-        self.define(&stmt.name.lexeme);
+        self.define(stmt.name.lexeme);
+        self.visit_block(&stmt.body);
+        self.end_scope();
+    }
+
+    fn visit_fun_decl(&mut self, stmt: &'a FunctionDecl) {
+        self.declare(&stmt.name);
+        self.define(stmt.name.lexeme);
         self.resolve_function(&stmt.params, &stmt.body, ScopeType::Function);
     }
 
-    fn resolve_function(&mut self, params: &'a Vec<Token>, stmts: &'a Vec<Statement>, scope_type: ScopeType) {
-        let old_scope = self.current_scope;
-        self.current_scope = scope_type;
-        self.begin_scope();
-        for param in params {
-            self.declare(&param);
-            self.define(&param.lexeme);
+    fn visit_lambda(&mut self, decl: &'a FunctionDecl) {
+        self.resolve_function(&decl.params, &decl.body, ScopeType::Function);
+    }
+
+    fn visit_import(&mut self, stmt: &'a ImportStatement) {
+        // Imports run while the importing script's top-level code runs, which their paths are relative to
+        if self.current_scope != ScopeType::Normal {
+            self.error(&stmt.keyword, "Can't import from inside a function.");
+        }
+        if let Some(alias) = &stmt.alias {
+            self.declare(alias);
+            self.define(alias.lexeme);
         }
-        stmts.iter().for_each(|stmt| self.resolve_stmt(stmt));
-        self.end_scope();
-        self.current_scope = old_scope;
     }
 
-    fn resolve_return_stmt(&mut self, stmt: &'a ReturnStatement) {
+    fn visit_return(&mut self, stmt: &'a ReturnStatement) {
         if self.current_scope == ScopeType::Normal {
-            self.has_err = true;
-            log::error_token(&stmt.return_token, "Can't return from top-level code.");
+            self.error(&stmt.return_token, "Can't return from top-level code.");
         }
-        if let Some(value) = &stmt.value {
-            if self.current_scope == ScopeType::Initializer {
-                self.has_err = true;
-                log::error_token(&stmt.return_token, "Can't return a value from an initializer.");
+        if stmt.value.is_some() {
+            match self.current_scope {
+                ScopeType::Initializer => {
+                    self.error(&stmt.return_token, "Can't return a value from an initializer.");
+                },
+                ScopeType::Getter if !stmt.implicit => {
+                    self.error(&stmt.return_token, "Can't return a value from a getter.");
+                },
+                _ => {},
             }
-            self.resolve_expr(value);
         }
+        walk_return(self, stmt);
     }
-}
 
-impl<'a> Resolver<'a> {
-    pub fn resolve_expr(&mut self, expr: &'a Expr) {
+    fn visit_expr(&mut self, expr: &'a Expr) {
         match expr {
-            Expr::Variable { name, height } => {
+            Expr::Variable { name, height, this_height } => {
                 if self.scopes.last().map(|s| s.get(&name.lexeme[..]) == Some(&false)).unwrap_or(false) {
-                    self.has_err = true;
-                    log::error_token(name, "Can't read local variable in its own initializer.");
+                    self.error(name, "Can't read local variable in its own initializer.");
+                }
+                match self.implicit_method(name.lexeme) {
+                    Some(height) => this_height.set(Some(height)),
+                    None => self.annotate(&name.lexeme, height),
                 }
-                self.annotate(&name.lexeme, height);
             },
             Expr::Asign { name, value, height } => {
-                self.resolve_expr(value);
+                self.visit_expr(value);
                 self.annotate(&name.lexeme, height);
             },
-            Expr::Unary { expr, .. } | Expr::Grouping(expr) => self.resolve_expr(expr),
-            Expr::LogicalOr { left, right } | Expr::LogicalAnd { left, right } | Expr::Binary { left, right, .. } => {
-                self.resolve_expr(left);
-                self.resolve_expr(right);
-            },
-            Expr::Call { callee, args, .. } => {
-                self.resolve_expr(callee);
-                args.iter().for_each(|arg| self.resolve_expr(arg));
-            },
-            Expr::Get { object, .. } => self.resolve_expr(object),
-            Expr::Set { object, value, .. } => {
-                self.resolve_expr(value);
-                self.resolve_expr(object);
-            },
             Expr::This { keyword, height } => {
                 if matches!(self.current_class, ClassType::None) {
-                    self.has_err = true;
-                    log::error_token(&keyword, "Can't use 'this' outside of a class.");
+                    self.error(&keyword, "Can't use 'this' outside of a class.");
                 } else {
                     self.annotate(&keyword.lexeme, height)
                 }
             },
-            Expr::Super { keyword, height, .. } => match self.current_class {
+            Expr::Super { keyword, method, height } => match self.current_class {
                 ClassType::None => {
-                    self.has_err = true;
-                    log::error_token(&keyword, "Can't use 'super' outside of a class.");
+                    self.error(&keyword, "Can't use 'super' outside of a class.");
                 },
                 ClassType::Class => {
-                    self.has_err = true;
-                    log::error_token(&keyword, "Can't use 'super' in a class with no superclass.");
+                    self.error(&keyword, "Can't use 'super' in a class with no superclass.");
+                },
+                ClassType::Subclass => {
+                    if method.lexeme == "init" {
+                        self.calls_super_init = true;
+                    }
+                    self.annotate(&keyword.lexeme, height)
                 },
-                ClassType::Subclass => self.annotate(&keyword.lexeme, height),
             },
-            Expr::Literal(_) => {},
+            expr => walk_expr(self, expr),
         }
     }
 }
 
 impl<'a> Resolver<'a> {
+    /// Each case body is its own scope, like a block.
+    fn resolve_case_body(&mut self, stmts: &'a [Statement]) {
+        self.begin_scope();
+        self.resolve_statements(stmts);
+        self.end_scope();
+    }
+
+    fn resolve_loop_jump(&mut self, stmt: &'a LoopJump) {
+        match stmt.label {
+            None if self.loops.is_empty() => {
+                self.error(&stmt.keyword, &format!("Can't use '{}' outside of a loop.", stmt.keyword.lexeme));
+            },
+            Some(label) if !self.loops.contains(&Some(label.lexeme)) => {
+                self.error(&label, &format!("No enclosing loop is labeled '{}'.", label.lexeme));
+            },
+            _ => {},
+        }
+    }
+
+    fn resolve_function(&mut self, params: &'a Vec<Token>, body: &'a FunctionBody, scope_type: ScopeType) {
+        let old_scope = self.current_scope;
+        self.current_scope = scope_type;
+        // A function body can't jump out of the loops around its declaration
+        let enclosing_loops = mem::take(&mut self.loops);
+        self.begin_scope();
+        for param in params {
+            self.declare(&param);
+            self.define(&param.lexeme);
+        }
+        match body {
+            FunctionBody::Block(stmts) => self.resolve_statements(stmts),
+            FunctionBody::Expr(expr) => self.visit_expr(expr),
+        }
+        self.end_scope();
+        self.loops = enclosing_loops;
+        self.current_scope = old_scope;
+    }
+
+    /// Resolves the statements of a scope. A function body only runs once the function is called, by which
+    /// time the functions declared after it in the same scope may exist too, so while its body is resolved
+    /// those names are visible as well. Code that runs before a declaration never sees its name, and calling
+    /// a function before the ones it refers to are declared still fails at runtime.
+    fn resolve_statements(&mut self, stmts: &'a [Statement]) {
+        for (i, stmt) in stmts.iter().enumerate() {
+            let Statement::FunDecl(decl) = stmt else {
+                self.visit_stmt(stmt);
+                continue;
+            };
+            self.declare(&decl.name);
+            self.define(decl.name.lexeme);
+            let mut later = vec![];
+            if let Some(scope) = self.scopes.last_mut() {
+                for stmt in &stmts[i + 1..] {
+                    if let Statement::FunDecl(FunctionDecl { name, .. }) = stmt {
+                        if !scope.contains_key(name.lexeme) {
+                            scope.insert(name.lexeme, true);
+                            later.push(name.lexeme);
+                        }
+                    }
+                }
+            }
+            self.resolve_function(&decl.params, &decl.body, ScopeType::Function);
+            if let Some(scope) = self.scopes.last_mut() {
+                for name in later {
+                    scope.remove(name);
+                }
+            }
+        }
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
     }
@@ -230,16 +416,30 @@ impl<'a> Resolver<'a> {
     }
 
     fn declare(&mut self, name: &'a Token) {
-        match self.scopes.last_mut() {
-            Some(scope) => {
-                if scope.contains_key(&name.lexeme[..]) {
-                    self.has_err = true;
-                    log::error_token(name, "Already a variable with this name in this scope.");
-                }
-                scope.insert(&name.lexeme, false);
-            },
-            None => {},
+        if self.warn_shadowing && self.scopes.iter().rev().skip(1).any(|s| s.contains_key(name.lexeme)) {
+            self.warning(name, "Declaration shadows a variable from an enclosing scope.");
+        }
+        let redeclared = self.scopes.last_mut().is_some_and(|scope| scope.insert(name.lexeme, false).is_some());
+        if redeclared {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        if self.errors >= self.max_errors {
+            if !self.gave_up {
+                self.gave_up = true;
+                log::too_many_errors();
+            }
+            return;
         }
+        self.errors += 1;
+        log::error_token(token, message);
+    }
+
+    fn warning(&mut self, token: &Token, message: &str) {
+        self.warnings += 1;
+        log::warning_token(token, message);
     }
 
     fn define(&mut self, name: &'a str) {
@@ -248,9 +448,61 @@ impl<'a> Resolver<'a> {
         }
     }
 
+    /// Finds the height of `this` when a bare name should refer to a method of the enclosing class,
+    /// which it does unless the name is declared somewhere inside the method.
+    fn implicit_method(&self, name: &str) -> Option<usize> {
+        if !self.implicit_methods || !self.class_methods.contains(name) {
+            return None;
+        }
+        let this_height = self.scopes.iter().rev().position(|scope| scope.contains_key("this"))?;
+        let shadowed = self.scopes.iter().rev().take(this_height).any(|scope| scope.contains_key(name));
+        (!shadowed).then_some(this_height)
+    }
+
     fn annotate(&mut self, name: &str, height: &Cell<Option<usize>>) {
         if let Some((index, _)) = self.scopes.iter().rev().enumerate().find(|(_, s)| s.contains_key(name)) {
             height.set(Some(index));
         }
     }
 }
+
+/// Calls, assignments and indexing may run code, and so may property reads since they can call getters.
+fn has_effect(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. } => false,
+        Expr::Unary { expr, .. } | Expr::Grouping { expr, .. } => has_effect(expr),
+        Expr::Binary { left, right, .. } | Expr::LogicalOr { left, right, .. } | Expr::LogicalAnd { left, right, .. } => has_effect(left) || has_effect(right),
+        Expr::List { elements, .. } | Expr::Interpolation { exprs: elements, .. } => elements.iter().any(has_effect),
+        Expr::Map { entries, .. } => entries.iter().any(|(key, value)| has_effect(key) || has_effect(value)),
+        _ => true,
+    }
+}
+
+/// The token a warning about the expression points at: the operator of an operation, otherwise its first token.
+fn leading_token<'e, 't>(expr: &'e Expr<'t>) -> &'e Token<'t> {
+    match expr {
+        Expr::Binary { operator, .. } | Expr::LogicalOr { operator, .. } | Expr::LogicalAnd { operator, .. } => operator,
+        Expr::Grouping { expr, .. } => leading_token(expr),
+        expr => expr.start(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{Parser, RecursiveDecendantParser};
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn resolution_stops_after_the_maximum_number_of_errors() {
+        let scanner = Scanner::new(b"{ var a; var a; }\n{ var b; var b; }\n{ var c; var c; }".to_vec());
+        let statements = RecursiveDecendantParser::new().parse(&scanner).unwrap();
+        let error_count = |max| {
+            let mut resolver = Resolver::new().max_errors(max);
+            statements.iter().for_each(|stmt| resolver.resolve_stmt(stmt));
+            resolver.error_count()
+        };
+        assert_eq!(error_count(2), 2);
+        assert_eq!(error_count(3), 3);
+    }
+}
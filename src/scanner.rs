@@ -1,5 +1,5 @@
 use core::str;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::fs::File;
 use std::io::{self, Read};
 
@@ -11,6 +11,8 @@ pub struct Scanner {
     current: Cell<usize>,
     line: Cell<u64>,
     has_error: Cell<bool>,
+    /// Brace depth inside each `${` interpolation being scanned, innermost last.
+    interpolations: RefCell<Vec<usize>>,
 }
 
 impl Scanner {
@@ -20,6 +22,7 @@ impl Scanner {
             current: Cell::new(0),
             line: Cell::new(1),
             has_error: Cell::new(false),
+            interpolations: RefCell::new(vec![]),
         }
     }
 }
@@ -54,26 +57,55 @@ impl<'t> Scanner {
     }
 
     pub fn next_token(&'t self) -> Token<'t> {
+        let token = self.scan_token();
+        token.ending_at(self.line.get(), self.current.get() as u64)
+    }
+
+    fn scan_token(&'t self) -> Token<'t> {
         loop {
             let line = self.line.get();
             let offset = self.current.get() as u64;
             let byte = self.advance();
             if byte.is_none() {
-                return Token::eof(line);
+                return Token::eof(line, offset);
             }
             use TokenType::*;
             let token: Token = match byte.unwrap() as char {
                 '(' => Token::symbol(LeftParen, "(", line, offset),
                 ')' => Token::symbol(RightParen, ")", line, offset),
-                '{' => Token::symbol(LeftBrace, "{", line, offset),
-                '}' => Token::symbol(RightBrace, "}", line, offset),
+                '{' => {
+                    if let Some(depth) = self.interpolations.borrow_mut().last_mut() {
+                        *depth += 1;
+                    }
+                    Token::symbol(LeftBrace, "{", line, offset)
+                },
+                // The brace closing an interpolation resumes the string it interrupted
+                '}' if self.interpolations.borrow().last() == Some(&0) => {
+                    self.interpolations.borrow_mut().pop();
+                    match self.string(line, offset) {
+                        Some(token) => token,
+                        None => continue,
+                    }
+                },
+                '}' => {
+                    if let Some(depth) = self.interpolations.borrow_mut().last_mut() {
+                        *depth -= 1;
+                    }
+                    Token::symbol(RightBrace, "}", line, offset)
+                },
+                '[' => Token::symbol(LeftBracket, "[", line, offset),
+                ']' => Token::symbol(RightBracket, "]", line, offset),
+                ':' => Token::symbol(Colon, ":", line, offset),
                 '+' => Token::symbol(Plus, "+", line, offset),
                 '-' => Token::symbol(Minus, "-", line, offset),
                 '.' => Token::symbol(Dot, ".", line, offset),
+                '?' if self.matchup(b'.') => Token::symbol(QuestionDot, "?.", line, offset),
+                '*' if self.matchup(b'*') => Token::symbol(StarStar, "**", line, offset),
                 '*' => Token::symbol(Star, "*", line, offset),
                 ',' => Token::symbol(Comma, ",", line, offset),
                 ';' => Token::symbol(SemiColon, ";", line, offset),
                 '=' if self.matchup(b'=') => Token::symbol(Equal, "==", line, offset),
+                '=' if self.matchup(b'>') => Token::symbol(Arrow, "=>", line, offset),
                 '=' => Token::symbol(Asign, "=", line, offset),
                 '!' if self.matchup(b'=') => Token::symbol(NotEqual, "!=", line, offset),
                 '!' => Token::symbol(Not, "!", line, offset),
@@ -86,7 +118,18 @@ impl<'t> Scanner {
                     continue;
                 },
                 '/' => Token::symbol(Div, "/", line, offset),
-                '"' => self.string(line, offset),
+                '\\' => Token::symbol(FloorDiv, "\\", line, offset),
+                '"' => match self.string(line, offset) {
+                    Some(token) => token,
+                    None => continue,
+                },
+                'r' if self.matchup(b'"') => match self.string(line, offset) {
+                    Some(token) => token,
+                    None => continue,
+                },
+                '0' if matches!(self.peek(), Some(b'x' | b'X')) && matches!(self.peek_offset(1), Some(c) if c.is_ascii_hexdigit() || c == b'_') => {
+                    return self.hex_number(line, offset)
+                },
                 '0'..='9' => return self.number(line, offset),
                 '\n' => continue,
                 'a'..='z' | 'A'..='Z' | '_' => return self.identifier(line, offset),
@@ -113,18 +156,43 @@ impl<'t> Scanner {
         c
     }
 
-    fn number(&self, line: u64, offset: u64) -> Token {
+    fn number(&self, line: u64, offset: u64) -> Token<'_> {
         loop {
             match self.peek() {
-                Some(b'0'..=b'9') => self.advance(),
+                Some(b'0'..=b'9' | b'_') => self.advance(),
                 Some(b'.') if matches!(self.peek_offset(1), Some(b'0'..=b'9')) => self.advance(),
                 _ => break,
             };
         }
         let lexeme = str::from_utf8(&self.source[offset as usize..self.current.get()]).unwrap();
+        self.check_separators(lexeme, u8::is_ascii_digit, line);
+        Token::number(lexeme, line, offset)
+    }
+
+    fn hex_number(&self, line: u64, offset: u64) -> Token<'_> {
+        self.advance();
+        while matches!(self.peek(), Some(c) if c.is_ascii_hexdigit() || c == b'_') {
+            self.advance();
+        }
+        let lexeme = str::from_utf8(&self.source[offset as usize..self.current.get()]).unwrap();
+        self.check_separators(lexeme, u8::is_ascii_hexdigit, line);
         Token::number(lexeme, line, offset)
     }
 
+    /// Reports a `_` in a number that doesn't sit between two digits, as in `1__0` or `1_`. The number
+    /// is still scanned, with its separators ignored, so scanning carries on.
+    fn check_separators(&self, lexeme: &str, is_digit: fn(&u8) -> bool, line: u64) {
+        let bytes = lexeme.as_bytes();
+        let misplaced = bytes.iter().enumerate().any(|(i, &b)| {
+            let between_digits = i > 0 && is_digit(&bytes[i - 1]) && bytes.get(i + 1).is_some_and(is_digit);
+            b == b'_' && !between_digits
+        });
+        if misplaced {
+            self.has_error.set(true);
+            log::error(line, &format!("Invalid numeric separator in '{lexeme}'."));
+        }
+    }
+
     fn identifier(&'t self, line: u64, offset: u64) -> Token<'t> {
         loop {
             match self.peek() {
@@ -136,20 +204,46 @@ impl<'t> Scanner {
         Token::textual(lexeme, line, offset)
     }
 
-    fn string(&'t self, line: u64, offset: u64) -> Token<'t> {
+    /// Scans a string whose opening quote was consumed, or the rest of one after an interpolation. An
+    /// unterminated string is reported and skipped up to the end of the line it starts on, so the rest of
+    /// the source is still scanned.
+    ///
+    /// `${` starts an interpolated expression and `\$` keeps a `$` from starting one. Raw strings have neither.
+    fn string(&'t self, line: u64, offset: u64) -> Option<Token<'t>> {
+        let raw = self.source[offset as usize] == b'r';
         loop {
             match self.advance() {
                 Some(b'"') => break,
+                Some(b'\\') if !raw && self.peek() == Some(b'$') => {
+                    self.advance();
+                },
+                Some(b'$') if !raw && self.matchup(b'{') => {
+                    self.interpolations.borrow_mut().push(0);
+                    let lexeme: &'t str = str::from_utf8(&self.source[offset as usize..self.current.get()]).unwrap();
+                    return Some(Token::interpolation(lexeme, line, offset));
+                },
                 Some(_) => continue,
                 None => {
-                    log::error(self.line.get(), "Unterminated string.");
+                    log::error(line, "Unterminated string.");
                     self.has_error.set(true);
-                    return Token::eof(line);
+                    self.interpolations.borrow_mut().clear();
+                    let start = offset as usize;
+                    let line_end = self.source[start..]
+                        .iter()
+                        .position(|&b| b == b'\n')
+                        .map_or(self.source.len(), |end| start + end);
+                    self.current.set(line_end);
+                    self.line.set(line);
+                    return None;
                 },
             }
         }
         let lexeme: &'t str = str::from_utf8(&self.source[offset as usize..self.current.get()]).unwrap();
-        Token::string(lexeme, line, offset)
+        // Strings have no escape sequences, so a raw string only differs by its `r` prefix
+        match lexeme.starts_with('r') {
+            true => Some(Token::raw_string(lexeme, line, offset)),
+            false => Some(Token::string(lexeme, line, offset)),
+        }
     }
 
     fn skip_line(&self) {
@@ -178,3 +272,94 @@ impl<'t> Scanner {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::TokenLiteral;
+
+    fn scan(source: &str) -> (Scanner, Vec<(TokenType, String, u64, u64)>) {
+        let scanner = Scanner::new(source.as_bytes().to_vec());
+        let tokens = scanner
+            .scan_all()
+            .iter()
+            .map(|token| (token.token_type, token.lexeme.to_string(), token.pos.offset, token.pos.end_offset))
+            .collect();
+        (scanner, tokens)
+    }
+
+    #[test]
+    fn offsets_point_at_the_start_of_each_lexeme() {
+        let source = "var x = 1.5 == 0xff;\n// comment\r\nprint \"a ${x + {1: 2}[1]} b\" r\"raw\" <= x ** 2 \\ 3 => _id;\n";
+        let (scanner, tokens) = scan(source);
+        assert!(!scanner.has_error());
+        for (token_type, lexeme, offset, end_offset) in &tokens {
+            assert!(source[*offset as usize..].starts_with(lexeme.as_str()), "{token_type:?} {lexeme:?} at {offset}");
+            assert_eq!(*end_offset, offset + lexeme.len() as u64, "{token_type:?} {lexeme:?}");
+        }
+    }
+
+    #[test]
+    fn crlf_ends_a_line_once() {
+        let scanner = Scanner::new(b"a\r\nb // note\r\n\r\n\"x\r\ny\"\r\nc".to_vec());
+        let lines: Vec<_> = scanner.scan_all().iter().map(|token| (token.lexeme.to_string(), token.pos.line)).collect();
+        assert!(!scanner.has_error());
+        let expected = [("a", 1), ("b", 2), ("\"x\r\ny\"", 4), ("c", 6), ("", 6)];
+        assert_eq!(lines, expected.map(|(lexeme, line)| (lexeme.to_string(), line)));
+    }
+
+    #[test]
+    fn tokens_spanning_lines_end_on_their_last_line() {
+        let source = "print \"one\ntwo\nthree\" + r\"raw\n\";\nx";
+        let scanner = Scanner::new(source.as_bytes().to_vec());
+        let positions: Vec<_> = scanner
+            .scan_all()
+            .iter()
+            .map(|token| (token.lexeme.to_string(), token.pos.line, token.pos.end_line, token.pos.end_offset))
+            .collect();
+        assert!(!scanner.has_error());
+        let expected = [
+            ("print", 1, 1, 5),
+            ("\"one\ntwo\nthree\"", 1, 3, 21),
+            ("+", 3, 3, 23),
+            ("r\"raw\n\"", 3, 4, 31),
+            (";", 4, 4, 32),
+            ("x", 5, 5, 34),
+            ("", 5, 5, 34),
+        ];
+        let expected = expected.map(|(lexeme, line, end_line, end)| (lexeme.to_string(), line, end_line, end));
+        assert_eq!(positions, expected);
+    }
+
+    #[test]
+    fn eof_is_positioned_past_the_last_byte() {
+        for source in ["", "print 1;", "// only a comment\n", "\"unterminated"] {
+            let (_, tokens) = scan(source);
+            let (token_type, _, offset, _) = tokens.last().unwrap();
+            assert_eq!(*token_type, TokenType::Eof);
+            assert_eq!(*offset, source.len() as u64, "{source:?}");
+        }
+    }
+
+    #[test]
+    fn separators_between_digits_are_ignored() {
+        for (source, expected) in [("1_000_000", 1_000_000.0), ("12.345_678", 12.345_678), ("1_0.0_1", 10.01), ("0xff_ff", 65535.0)] {
+            let scanner = Scanner::new(source.as_bytes().to_vec());
+            let token = scanner.next_token();
+            assert!(!scanner.has_error(), "{source:?}");
+            assert_eq!(token.lexeme, source);
+            assert_eq!(token.literal, TokenLiteral::Number(expected), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn misplaced_separators_are_errors() {
+        for source in ["1__0", "1_", "1_.5", "0x_ff", "0xff_"] {
+            let (scanner, _) = scan(source);
+            assert!(scanner.has_error(), "{source:?}");
+        }
+        // A leading underscore starts an identifier rather than a number
+        let (scanner, tokens) = scan("_1");
+        assert!(!scanner.has_error());
+        assert_eq!(tokens[0].0, TokenType::Identifier);
+    }
+}
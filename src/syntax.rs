@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::cell::{Cell, RefCell};
 use std::fmt::Display;
 use std::rc::Rc;
@@ -5,7 +6,8 @@ use std::rc::Rc;
 use crate::interpreter::tree_walker::class::Class;
 use crate::interpreter::tree_walker::function::{Function, NativeFunction};
 use crate::interpreter::tree_walker::instance::Instance;
-use crate::token::Token;
+use crate::interpreter::tree_walker::module::Module;
+use crate::token::{Token, TokenLiteral, TokenType};
 
 pub type BoxedExpr<'t> = Box<Expr<'t>>;
 pub type BoxedStatement<'t> = Box<Statement<'t>>;
@@ -14,13 +16,22 @@ pub type BoxedStatement<'t> = Box<Statement<'t>>;
 pub enum Statement<'t> {
     FunDecl(FunctionDecl<'t>),
     VarDecl(VariableDecl<'t>),
+    DestructureDecl(DestructureDecl<'t>),
     ClassDecl(ClassDecl<'t>),
     Print(PrintStatement<'t>),
     Expr(ExpressionStatement<'t>),
     Block(BlockStatement<'t>),
     If(IfStatemnet<'t>),
     While(WhileStatement<'t>),
+    DoWhile(WhileStatement<'t>),
     Return(ReturnStatement<'t>),
+    Break(LoopJump<'t>),
+    Continue(LoopJump<'t>),
+    Try(TryStatement<'t>),
+    Using(UsingStatement<'t>),
+    Throw(ThrowStatement<'t>),
+    Switch(SwitchStatement<'t>),
+    Import(ImportStatement<'t>),
 }
 
 #[derive(Debug, Clone)]
@@ -34,7 +45,16 @@ pub struct ClassDecl<'t> {
 pub struct FunctionDecl<'t> {
     pub name: Token<'t>,
     pub params: Vec<Token<'t>>,
-    pub body: Vec<Statement<'t>>,
+    pub body: FunctionBody<'t>,
+    pub is_getter: bool,
+}
+
+/// A block body produces its value through `return`, while an expression body, written after `=>`,
+/// is the value itself.
+#[derive(Debug, Clone)]
+pub enum FunctionBody<'t> {
+    Block(Vec<Statement<'t>>),
+    Expr(Expr<'t>),
 }
 
 #[derive(Debug, Clone)]
@@ -43,10 +63,17 @@ pub struct VariableDecl<'t> {
     pub initializer: Option<Expr<'t>>,
 }
 
+#[derive(Debug, Clone)]
+pub struct DestructureDecl<'t> {
+    pub bracket: Token<'t>,
+    pub names: Vec<Token<'t>>,
+    pub initializer: Expr<'t>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PrintStatement<'t> {
     pub print_token: Token<'t>,
-    pub expr: Expr<'t>,
+    pub exprs: Vec<Expr<'t>>,
 }
 
 #[derive(Debug, Clone)]
@@ -61,16 +88,92 @@ pub struct IfStatemnet<'t> {
     pub else_branch: Option<BoxedStatement<'t>>,
 }
 
+/// Binds `name` for the body and calls the bound instance's `close` method once the body is left, however it is left.
+#[derive(Debug, Clone)]
+pub struct UsingStatement<'t> {
+    pub keyword: Token<'t>,
+    pub name: Token<'t>,
+    pub initializer: Expr<'t>,
+    pub body: BlockStatement<'t>,
+}
+
+/// Has a `catch` clause, a `finally` block or both.
+#[derive(Debug, Clone)]
+pub struct TryStatement<'t> {
+    pub try_block: BlockStatement<'t>,
+    pub catch: Option<CatchClause<'t>>,
+    pub finally_block: Option<BlockStatement<'t>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CatchClause<'t> {
+    pub name: Token<'t>,
+    pub block: BlockStatement<'t>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwitchStatement<'t> {
+    pub keyword: Token<'t>,
+    pub subject: Expr<'t>,
+    pub cases: Vec<SwitchCase<'t>>,
+    pub default: Option<Vec<Statement<'t>>>,
+}
+
+/// A case runs when any of its patterns matches the subject.
+#[derive(Debug, Clone)]
+pub struct SwitchCase<'t> {
+    pub patterns: Vec<CasePattern<'t>>,
+    pub body: Vec<Statement<'t>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CasePattern<'t> {
+    /// Matches a subject equal to the value, like `case 1:`.
+    Value(Expr<'t>),
+    /// Matches when comparing the subject with the value is true, like `case > 10:`.
+    Guard { operator: Token<'t>, value: Expr<'t> },
+}
+
+/// Runs another script, at most once however often it is imported. Its path is relative to the directory
+/// of the importing script.
+#[derive(Debug, Clone)]
+pub struct ImportStatement<'t> {
+    pub keyword: Token<'t>,
+    /// The string literal holding the path.
+    pub path: Token<'t>,
+    /// The name given with `as`, which the script's declarations are reached through instead of becoming
+    /// globals.
+    pub alias: Option<Token<'t>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThrowStatement<'t> {
+    pub keyword: Token<'t>,
+    pub value: Expr<'t>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ReturnStatement<'t> {
     pub return_token: Token<'t>,
     pub value: Option<Expr<'t>>,
+    /// Set for the return a getter's trailing expression becomes, which has no `return` keyword of its own.
+    pub implicit: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct WhileStatement<'t> {
+    pub label: Option<Token<'t>>,
     pub condition: Expr<'t>,
     pub body: BoxedStatement<'t>,
+    /// The increment of a desugared `for`, kept apart from the body so `continue` still runs it.
+    pub increment: Option<Expr<'t>>,
+}
+
+/// A `break` or `continue`, which leaves the innermost loop unless it names the label of an enclosing one.
+#[derive(Debug, Clone)]
+pub struct LoopJump<'t> {
+    pub keyword: Token<'t>,
+    pub label: Option<Token<'t>>,
 }
 
 #[derive(Debug, Clone)]
@@ -94,18 +197,28 @@ pub enum Expr<'t> {
         operator: Token<'t>,
         expr: BoxedExpr<'t>,
     },
-    Grouping(BoxedExpr<'t>),
-    Literal(Literal<'t>),
+    Grouping {
+        paren: Token<'t>,
+        expr: BoxedExpr<'t>,
+    },
+    Literal {
+        token: Token<'t>,
+        value: Literal<'t>,
+    },
     Variable {
         name: Token<'t>,
         height: Cell<Option<usize>>,
+        /// Height of `this` when the name is resolved as an implicit call to a method of the enclosing class.
+        this_height: Cell<Option<usize>>,
     },
     LogicalOr {
         left: BoxedExpr<'t>,
+        operator: Token<'t>,
         right: BoxedExpr<'t>,
     },
     LogicalAnd {
         left: BoxedExpr<'t>,
+        operator: Token<'t>,
         right: BoxedExpr<'t>,
     },
     Call {
@@ -116,6 +229,8 @@ pub enum Expr<'t> {
     Get {
         object: BoxedExpr<'t>,
         name: Token<'t>,
+        /// Written `?.`, which gives `nil` rather than failing when the object is `nil`.
+        optional: bool,
     },
     Set {
         object: BoxedExpr<'t>,
@@ -131,25 +246,137 @@ pub enum Expr<'t> {
         method: Token<'t>,
         height: Cell<Option<usize>>,
     },
+    List {
+        bracket: Token<'t>,
+        elements: Vec<Expr<'t>>,
+    },
+    Map {
+        brace: Token<'t>,
+        entries: Vec<(Expr<'t>, Expr<'t>)>,
+    },
+    /// A string with embedded expressions; there is always one more segment than expressions.
+    Interpolation {
+        /// The string token the interpolation starts with.
+        start: Token<'t>,
+        segments: Vec<Cow<'t, str>>,
+        exprs: Vec<Expr<'t>>,
+    },
+    Index {
+        object: BoxedExpr<'t>,
+        bracket: Token<'t>,
+        index: BoxedExpr<'t>,
+    },
+    IndexSet {
+        object: BoxedExpr<'t>,
+        bracket: Token<'t>,
+        index: BoxedExpr<'t>,
+        value: BoxedExpr<'t>,
+    },
+    Slice {
+        object: BoxedExpr<'t>,
+        bracket: Token<'t>,
+        start: Option<BoxedExpr<'t>>,
+        end: Option<BoxedExpr<'t>>,
+    },
+    /// An anonymous function written as a block after the parentheses of a call, which passes it as
+    /// the last argument. It is named `lambda` and its name token sits at the opening brace.
+    Lambda {
+        decl: Box<FunctionDecl<'t>>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialOrd)]
 pub enum Value<'a, 't> {
     Number(f64),
-    String(String),
+    String(Rc<str>),
     Class(Rc<Class<'a, 't>>),
     Function(Rc<Function<'a, 't>>),
-    NativeFunction(Rc<NativeFunction<'t, 'a>>),
+    NativeFunction(Rc<NativeFunction<'a, 't>>),
     Instance(Rc<RefCell<Instance<'a, 't>>>),
+    Module(Rc<Module<'a, 't>>),
+    List(Rc<RefCell<Vec<Value<'a, 't>>>>),
+    /// Entries in insertion order; keys are compared with `==`.
+    Map(Rc<RefCell<Vec<(Value<'a, 't>, Value<'a, 't>)>>>),
     Bool(bool),
     Nil,
 }
 
+impl Value<'_, '_> {
+    /// The name of the value's type as scripts see it, like `number` or `instance`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Class(_) => "class",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Instance(_) => "instance",
+            Value::Module(_) => "module",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+        }
+    }
+}
+
+/// Lists and maps are equal when their contents are. A pair of collections reached again while their own
+/// contents are being compared, which only cyclic data allows, is compared by reference instead, so
+/// comparing cyclic collections ends.
+impl PartialEq for Value<'_, '_> {
+    fn eq(&self, other: &Self) -> bool {
+        values_equal(self, other, &mut vec![])
+    }
+}
+
+/// `comparing` holds the pairs of collections whose contents are being compared further up.
+fn values_equal<'a, 't>(left: &Value<'a, 't>, right: &Value<'a, 't>, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+    match (left, right) {
+        (Value::List(l), Value::List(r)) => contents_equal(l, r, comparing, |l, r, comparing| {
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| values_equal(l, r, comparing))
+        }),
+        (Value::Map(l), Value::Map(r)) => contents_equal(l, r, comparing, |l, r, comparing| {
+            l.len() == r.len()
+                && l.iter()
+                    .zip(r.iter())
+                    .all(|((lk, lv), (rk, rv))| values_equal(lk, rk, comparing) && values_equal(lv, rv, comparing))
+        }),
+        (Value::Number(l), Value::Number(r)) => l == r,
+        (Value::String(l), Value::String(r)) => l == r,
+        (Value::Class(l), Value::Class(r)) => l == r,
+        (Value::Function(l), Value::Function(r)) => l == r,
+        (Value::NativeFunction(l), Value::NativeFunction(r)) => l == r,
+        (Value::Instance(l), Value::Instance(r)) => l == r,
+        (Value::Module(l), Value::Module(r)) => l == r,
+        (Value::Bool(l), Value::Bool(r)) => l == r,
+        (Value::Nil, Value::Nil) => true,
+        _ => false,
+    }
+}
+
+fn contents_equal<T>(
+    left: &Rc<RefCell<T>>,
+    right: &Rc<RefCell<T>>,
+    comparing: &mut Vec<(*const (), *const ())>,
+    compare: impl FnOnce(&T, &T, &mut Vec<(*const (), *const ())>) -> bool,
+) -> bool {
+    if Rc::ptr_eq(left, right) {
+        return true;
+    }
+    let pair = (Rc::as_ptr(left).cast::<()>(), Rc::as_ptr(right).cast::<()>());
+    if comparing.contains(&pair) {
+        return false;
+    }
+    comparing.push(pair);
+    let equal = compare(&left.borrow(), &right.borrow(), comparing);
+    comparing.pop();
+    equal
+}
+
 impl<'a> From<&Literal<'a>> for Value<'_, '_> {
     fn from(value: &Literal) -> Self {
         match value {
             Literal::Number(n) => Value::Number(*n),
-            Literal::String(_) => Value::String(value.to_string()),
+            Literal::String(s) => Value::String(Rc::from(s.as_ref())),
             Literal::Bool(_) => Value::Bool(value.to_string().parse().unwrap()),
             Literal::Nil => Value::Nil,
         }
@@ -159,7 +386,8 @@ impl<'a> From<&Literal<'a>> for Value<'_, '_> {
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Literal<'t> {
     Number(f64),
-    String(&'t str),
+    /// Borrowed from the source unless an escape had to be removed.
+    String(Cow<'t, str>),
     Bool(bool),
     Nil,
 }
@@ -175,24 +403,168 @@ impl Display for Literal<'_> {
     }
 }
 
+/// A list or map that contains itself, directly or through other collections, shows as `[...]` or `{...}`
+/// where it comes around again, so printing it ends.
 impl Display for Value<'_, '_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Value::Number(n) => write!(f, "{n}"),
-            Value::String(s) => write!(f, "{s}"),
-            Value::Bool(b) => write!(f, "{b}"),
-            Value::Nil => write!(f, "nil"),
-            Value::Class(class) => write!(f, "{class}"),
-            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
-            Value::Function(function) => write!(f, "{function}"),
-            Value::NativeFunction(native_function) => write!(f, "{native_function}"),
+        let format = NumberFormat { significant_digits: f.precision(), ..NumberFormat::default() };
+        fmt_value(self, f, &format, &mut vec![])
+    }
+}
+
+impl<'a, 't> Value<'a, 't> {
+    /// Shows the value like its `Display` does, with the numbers in it shown in `format`.
+    pub fn formatted<'v>(&'v self, format: &'v NumberFormat) -> FormattedValue<'v, 'a, 't> {
+        FormattedValue { value: self, format }
+    }
+}
+
+pub struct FormattedValue<'v, 'a, 't> {
+    value: &'v Value<'a, 't>,
+    format: &'v NumberFormat,
+}
+
+impl Display for FormattedValue<'_, '_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_value(self.value, f, self.format, &mut vec![])
+    }
+}
+
+fn fmt_value(value: &Value, f: &mut std::fmt::Formatter<'_>, format: &NumberFormat, showing: &mut Vec<*const ()>) -> std::fmt::Result {
+    match value {
+        Value::Number(n) => write!(f, "{}", format.format(*n)),
+        Value::String(s) => write!(f, "{s}"),
+        Value::Bool(b) => write!(f, "{b}"),
+        Value::Nil => write!(f, "nil"),
+        Value::Class(class) => write!(f, "{class}"),
+        Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+        Value::Module(module) => write!(f, "{module}"),
+        Value::Function(function) => write!(f, "{function}"),
+        Value::NativeFunction(native_function) => write!(f, "{native_function}"),
+        Value::List(list) => {
+            let pointer = Rc::as_ptr(list).cast::<()>();
+            if showing.contains(&pointer) {
+                return write!(f, "[...]");
+            }
+            showing.push(pointer);
+            write!(f, "[")?;
+            for (i, element) in list.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                fmt_value(element, f, format, showing)?;
+            }
+            showing.pop();
+            write!(f, "]")
+        },
+        Value::Map(map) => {
+            let pointer = Rc::as_ptr(map).cast::<()>();
+            if showing.contains(&pointer) {
+                return write!(f, "{{...}}");
+            }
+            showing.push(pointer);
+            write!(f, "{{")?;
+            for (i, (key, value)) in map.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                fmt_value(key, f, format, showing)?;
+                write!(f, ": ")?;
+                fmt_value(value, f, format, showing)?;
+            }
+            showing.pop();
+            write!(f, "}}")
+        },
+    }
+}
+
+/// Numbers at least this large in magnitude are shown in scientific notation by default, like `1e16`.
+pub const SCIENTIFIC_FROM: f64 = 1e16;
+/// Non-zero numbers smaller than this in magnitude are shown in scientific notation by default, like `1e-5`.
+pub const SCIENTIFIC_BELOW: f64 = 1e-4;
+
+/// How `print` shows numbers. By default they aren't rounded and switch to scientific notation where `{:?}`
+/// does, so values print like the literals of a parsed expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumberFormat {
+    pub significant_digits: Option<usize>,
+    /// Numbers at least this large in magnitude are shown in scientific notation.
+    pub scientific_from: f64,
+    /// Non-zero numbers smaller than this in magnitude are shown in scientific notation.
+    pub scientific_below: f64,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self { significant_digits: None, scientific_from: SCIENTIFIC_FROM, scientific_below: SCIENTIFIC_BELOW }
+    }
+}
+
+impl NumberFormat {
+    /// Rounds before choosing the notation, so a number rounded up to a threshold is shown past it.
+    pub fn format(&self, n: f64) -> String {
+        let rounded = match self.significant_digits.filter(|&d| d > 0 && n.is_finite() && n != 0.0) {
+            Some(digits) => round_significant(n, digits),
+            None => n,
+        };
+        let magnitude = rounded.abs();
+        match magnitude.is_finite() && magnitude != 0.0 && !(self.scientific_below..self.scientific_from).contains(&magnitude) {
+            true => format!("{rounded:e}"),
+            false => format!("{rounded}"),
         }
     }
 }
 
+/// Shows a number the way `print` does by default, rounded to `significant_digits` when given.
+pub fn format_number(n: f64, significant_digits: Option<usize>) -> String {
+    NumberFormat { significant_digits, ..NumberFormat::default() }.format(n)
+}
+
+fn round_significant(n: f64, digits: usize) -> f64 {
+    let exponent = digits as i32 - (n.abs().log10().floor() as i32 + 1);
+    let scale = 10f64.powi(exponent.abs());
+    if !scale.is_finite() {
+        return n;
+    }
+    match exponent >= 0 {
+        true => (n * scale).round() / scale,
+        false => (n / scale).round() * scale,
+    }
+}
+
+impl<'t> Statement<'t> {
+    /// The line the statement starts on, taken from its first part that keeps a token. Only an empty block,
+    /// or a `try` whose block is empty, has none.
+    pub fn line(&self) -> Option<u64> {
+        let token = match self {
+            Statement::FunDecl(decl) => &decl.name,
+            Statement::VarDecl(decl) => &decl.name,
+            Statement::DestructureDecl(decl) => &decl.bracket,
+            Statement::ClassDecl(decl) => &decl.name,
+            Statement::Print(stmt) => &stmt.print_token,
+            Statement::Expr(stmt) => stmt.expr.start(),
+            Statement::Block(block) => return block.statements.first()?.line(),
+            Statement::If(stmt) => stmt.condition.start(),
+            Statement::While(stmt) => stmt.condition.start(),
+            Statement::DoWhile(stmt) => return stmt.body.line(),
+            Statement::Return(stmt) => &stmt.return_token,
+            Statement::Break(jump) | Statement::Continue(jump) => &jump.keyword,
+            Statement::Try(stmt) => return stmt.try_block.statements.first()?.line(),
+            Statement::Using(stmt) => &stmt.keyword,
+            Statement::Throw(stmt) => &stmt.keyword,
+            Statement::Switch(stmt) => &stmt.keyword,
+            Statement::Import(stmt) => &stmt.keyword,
+        };
+        Some(token.pos.line)
+    }
+}
+
 impl<'t> Expr<'t> {
-    pub fn grouping(expr: Expr<'t>) -> Self {
-        Self::Grouping(BoxedExpr::new(expr))
+    pub fn grouping(paren: Token<'t>, expr: Expr<'t>) -> Self {
+        Self::Grouping {
+            paren,
+            expr: BoxedExpr::new(expr),
+        }
     }
 
     pub fn unary(operator: Token<'t>, expr: Expr<'t>) -> Self {
@@ -211,7 +583,11 @@ impl<'t> Expr<'t> {
     }
 
     pub fn variable(name: Token<'t>, height: Cell<Option<usize>>) -> Self {
-        Self::Variable { name, height }
+        Self::Variable {
+            name,
+            height,
+            this_height: Cell::new(None),
+        }
     }
 
     pub fn assign(name: Token<'t>, value: Expr<'t>) -> Self {
@@ -222,16 +598,18 @@ impl<'t> Expr<'t> {
         }
     }
 
-    pub fn or(left: Expr<'t>, right: Expr<'t>) -> Self {
+    pub fn or(left: Expr<'t>, operator: Token<'t>, right: Expr<'t>) -> Self {
         Self::LogicalOr {
             left: BoxedExpr::new(left),
+            operator,
             right: BoxedExpr::new(right),
         }
     }
 
-    pub fn and(left: Expr<'t>, right: Expr<'t>) -> Self {
+    pub fn and(left: Expr<'t>, operator: Token<'t>, right: Expr<'t>) -> Self {
         Self::LogicalAnd {
             left: BoxedExpr::new(left),
+            operator,
             right: BoxedExpr::new(right),
         }
     }
@@ -248,6 +626,15 @@ impl<'t> Expr<'t> {
         Self::Get {
             object: BoxedExpr::new(object),
             name,
+            optional: false,
+        }
+    }
+
+    pub fn optional_get(object: Expr<'t>, name: Token<'t>) -> Self {
+        Self::Get {
+            object: BoxedExpr::new(object),
+            name,
+            optional: true,
         }
     }
 
@@ -266,8 +653,8 @@ impl<'t> Expr<'t> {
         }
     }
 
-    pub fn literal(literal: Literal<'t>) -> Self {
-        Self::Literal(literal)
+    pub fn literal(token: Token<'t>, value: Literal<'t>) -> Self {
+        Self::Literal { token, value }
     }
 
     pub fn super_(keyword: Token<'t>, method: Token<'t>) -> Self {
@@ -277,6 +664,82 @@ impl<'t> Expr<'t> {
             height: Cell::new(None),
         }
     }
+
+    pub fn list(bracket: Token<'t>, elements: Vec<Expr<'t>>) -> Self {
+        Self::List { bracket, elements }
+    }
+
+    pub fn map(brace: Token<'t>, entries: Vec<(Expr<'t>, Expr<'t>)>) -> Self {
+        Self::Map { brace, entries }
+    }
+
+    pub fn interpolation(start: Token<'t>, segments: Vec<Cow<'t, str>>, exprs: Vec<Expr<'t>>) -> Self {
+        Self::Interpolation { start, segments, exprs }
+    }
+
+    pub fn index(object: Expr<'t>, bracket: Token<'t>, index: Expr<'t>) -> Self {
+        Self::Index {
+            object: BoxedExpr::new(object),
+            bracket,
+            index: BoxedExpr::new(index),
+        }
+    }
+
+    pub fn index_set(object: BoxedExpr<'t>, bracket: Token<'t>, index: BoxedExpr<'t>, value: Expr<'t>) -> Self {
+        Self::IndexSet {
+            object,
+            bracket,
+            index,
+            value: BoxedExpr::new(value),
+        }
+    }
+
+    pub fn slice(object: Expr<'t>, bracket: Token<'t>, start: Option<Expr<'t>>, end: Option<Expr<'t>>) -> Self {
+        Self::Slice {
+            object: BoxedExpr::new(object),
+            bracket,
+            start: start.map(BoxedExpr::new),
+            end: end.map(BoxedExpr::new),
+        }
+    }
+
+    pub fn lambda(brace: Token<'t>, params: Vec<Token<'t>>, body: Vec<Statement<'t>>) -> Self {
+        let name = Token::new(TokenType::Identifier, "lambda", TokenLiteral::NoValue, brace.pos.line, brace.pos.offset);
+        Self::Lambda {
+            decl: Box::new(FunctionDecl {
+                name,
+                params,
+                body: FunctionBody::Block(body),
+                is_getter: false,
+            }),
+        }
+    }
+
+    /// Whether the expression's source ends with the closing brace of a trailing lambda, after which an
+    /// expression statement needs no `;`, like a block statement.
+    pub fn ends_with_lambda(&self) -> bool {
+        match self {
+            Expr::Call { args, .. } => matches!(args.last(), Some(Expr::Lambda { .. })),
+            Expr::Asign { value: last, .. } | Expr::Set { value: last, .. } | Expr::IndexSet { value: last, .. } => last.ends_with_lambda(),
+            Expr::Binary { right: last, .. } | Expr::LogicalOr { right: last, .. } | Expr::LogicalAnd { right: last, .. } => last.ends_with_lambda(),
+            Expr::Unary { expr: last, .. } => last.ends_with_lambda(),
+            _ => false,
+        }
+    }
+
+    /// The first token of the expression, which places it in the source.
+    pub fn start(&self) -> &Token<'t> {
+        match self {
+            Expr::Asign { name: token, .. } | Expr::Variable { name: token, .. } | Expr::Literal { token, .. } => token,
+            Expr::Unary { operator: token, .. } | Expr::Grouping { paren: token, .. } => token,
+            Expr::This { keyword: token, .. } | Expr::Super { keyword: token, .. } => token,
+            Expr::List { bracket: token, .. } | Expr::Map { brace: token, .. } | Expr::Interpolation { start: token, .. } => token,
+            Expr::Binary { left: first, .. } | Expr::LogicalOr { left: first, .. } | Expr::LogicalAnd { left: first, .. } => first.start(),
+            Expr::Call { callee: first, .. } | Expr::Get { object: first, .. } | Expr::Set { object: first, .. } => first.start(),
+            Expr::Index { object: first, .. } | Expr::IndexSet { object: first, .. } | Expr::Slice { object: first, .. } => first.start(),
+            Expr::Lambda { decl } => &decl.name,
+        }
+    }
 }
 
 impl Display for Expr<'_> {
@@ -296,16 +759,16 @@ impl Display for Expr<'_> {
                 operator: Token { lexeme, .. },
                 expr,
             } => write!(f, "({lexeme} {expr})"),
-            Expr::Grouping(expr) => write!(f, "(group {expr})"),
-            Expr::Literal(Literal::Bool(b)) => write!(f, "{b}"),
-            Expr::Literal(Literal::String(s)) => write!(f, "{s}"),
-            Expr::Literal(Literal::Nil) => write!(f, "nil"),
-            Expr::Literal(Literal::Number(n)) => write!(f, "{n:?}"),
+            Expr::Grouping { expr, .. } => write!(f, "(group {expr})"),
+            Expr::Literal { value: Literal::Bool(b), .. } => write!(f, "{b}"),
+            Expr::Literal { value: Literal::String(s), .. } => write!(f, "{s}"),
+            Expr::Literal { value: Literal::Nil, .. } => write!(f, "nil"),
+            Expr::Literal { value: Literal::Number(n), .. } => write!(f, "{n:?}"),
             Expr::Variable {
                 name: Token { lexeme, .. }, ..
             } => write!(f, "{lexeme}"),
-            Expr::LogicalOr { left, right } => write!(f, "(or {left} {right})"),
-            Expr::LogicalAnd { left, right } => write!(f, "(and {left} {right})"),
+            Expr::LogicalOr { left, right, .. } => write!(f, "(or {left} {right})"),
+            Expr::LogicalAnd { left, right, .. } => write!(f, "(and {left} {right})"),
             Expr::Call { callee, args, .. } => {
                 write!(f, "(call {callee} ")?;
                 if !args.is_empty() {
@@ -319,7 +782,8 @@ impl Display for Expr<'_> {
             Expr::Get {
                 object,
                 name: Token { lexeme, .. },
-            } => write!(f, "(get {object} {lexeme})"),
+                optional,
+            } => write!(f, "({} {object} {lexeme})", if *optional { "get?" } else { "get" }),
             Expr::Set {
                 object,
                 name: Token { lexeme, .. },
@@ -329,6 +793,548 @@ impl Display for Expr<'_> {
             Expr::Super {
                 method: Token { lexeme, .. }, ..
             } => write!(f, "(super {lexeme})"),
+            Expr::List { elements, .. } => {
+                write!(f, "(list")?;
+                for element in elements {
+                    write!(f, " {element}")?;
+                }
+                write!(f, ")")
+            },
+            Expr::Map { entries, .. } => {
+                write!(f, "(map")?;
+                for (key, value) in entries {
+                    write!(f, " {key} {value}")?;
+                }
+                write!(f, ")")
+            },
+            Expr::Interpolation { segments, exprs, .. } => {
+                write!(f, "(interpolation {}", segments[0])?;
+                for (expr, segment) in exprs.iter().zip(&segments[1..]) {
+                    write!(f, " {expr} {segment}")?;
+                }
+                write!(f, ")")
+            },
+            Expr::Index { object, index, .. } => write!(f, "(index {object} {index})"),
+            Expr::IndexSet { object, index, value, .. } => write!(f, "(index-set {object} {index} {value})"),
+            Expr::Slice { object, start, end, .. } => {
+                write!(f, "(slice {object}")?;
+                for bound in [start, end] {
+                    match bound {
+                        Some(expr) => write!(f, " {expr}")?,
+                        None => write!(f, " nil")?,
+                    }
+                }
+                write!(f, ")")
+            },
+            Expr::Lambda { decl } => {
+                write!(f, "(lambda")?;
+                for param in &decl.params {
+                    write!(f, " {}", param.lexeme)?;
+                }
+                write!(f, ")")
+            },
         }
     }
 }
+
+/// Walks a syntax tree without changing it. Every method visits the children of its node by default, so
+/// an implementation only overrides the nodes it cares about, calling the matching `walk_*` function
+/// where it still wants to descend.
+pub trait Visitor<'a> {
+    fn visit_stmt(&mut self, stmt: &'a Statement) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_fun_decl(&mut self, decl: &'a FunctionDecl) {
+        walk_fun_decl(self, decl);
+    }
+
+    fn visit_var_decl(&mut self, decl: &'a VariableDecl) {
+        walk_var_decl(self, decl);
+    }
+
+    fn visit_destructure_decl(&mut self, decl: &'a DestructureDecl) {
+        walk_destructure_decl(self, decl);
+    }
+
+    fn visit_class_decl(&mut self, decl: &'a ClassDecl) {
+        walk_class_decl(self, decl);
+    }
+
+    fn visit_print(&mut self, stmt: &'a PrintStatement) {
+        walk_print(self, stmt);
+    }
+
+    fn visit_expr_stmt(&mut self, stmt: &'a ExpressionStatement) {
+        self.visit_expr(&stmt.expr);
+    }
+
+    fn visit_block(&mut self, stmt: &'a BlockStatement) {
+        walk_block(self, stmt);
+    }
+
+    fn visit_if(&mut self, stmt: &'a IfStatemnet) {
+        walk_if(self, stmt);
+    }
+
+    fn visit_while(&mut self, stmt: &'a WhileStatement) {
+        walk_while(self, stmt);
+    }
+
+    fn visit_do_while(&mut self, stmt: &'a WhileStatement) {
+        walk_do_while(self, stmt);
+    }
+
+    fn visit_return(&mut self, stmt: &'a ReturnStatement) {
+        walk_return(self, stmt);
+    }
+
+    fn visit_break(&mut self, _stmt: &'a LoopJump) {}
+
+    fn visit_continue(&mut self, _stmt: &'a LoopJump) {}
+
+    fn visit_try(&mut self, stmt: &'a TryStatement) {
+        walk_try(self, stmt);
+    }
+
+    fn visit_catch(&mut self, catch: &'a CatchClause) {
+        walk_catch(self, catch);
+    }
+
+    fn visit_using(&mut self, stmt: &'a UsingStatement) {
+        walk_using(self, stmt);
+    }
+
+    fn visit_throw(&mut self, stmt: &'a ThrowStatement) {
+        self.visit_expr(&stmt.value);
+    }
+
+    fn visit_switch(&mut self, stmt: &'a SwitchStatement) {
+        walk_switch(self, stmt);
+    }
+
+    fn visit_import(&mut self, _stmt: &'a ImportStatement) {}
+
+    fn visit_lambda(&mut self, decl: &'a FunctionDecl) {
+        walk_fun_decl(self, decl);
+    }
+
+    fn visit_expr(&mut self, expr: &'a Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+pub fn walk_stmt<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a Statement) {
+    match stmt {
+        Statement::FunDecl(decl) => visitor.visit_fun_decl(decl),
+        Statement::VarDecl(decl) => visitor.visit_var_decl(decl),
+        Statement::DestructureDecl(decl) => visitor.visit_destructure_decl(decl),
+        Statement::ClassDecl(decl) => visitor.visit_class_decl(decl),
+        Statement::Print(stmt) => visitor.visit_print(stmt),
+        Statement::Expr(stmt) => visitor.visit_expr_stmt(stmt),
+        Statement::Block(stmt) => visitor.visit_block(stmt),
+        Statement::If(stmt) => visitor.visit_if(stmt),
+        Statement::While(stmt) => visitor.visit_while(stmt),
+        Statement::DoWhile(stmt) => visitor.visit_do_while(stmt),
+        Statement::Return(stmt) => visitor.visit_return(stmt),
+        Statement::Break(stmt) => visitor.visit_break(stmt),
+        Statement::Continue(stmt) => visitor.visit_continue(stmt),
+        Statement::Try(stmt) => visitor.visit_try(stmt),
+        Statement::Using(stmt) => visitor.visit_using(stmt),
+        Statement::Throw(stmt) => visitor.visit_throw(stmt),
+        Statement::Switch(stmt) => visitor.visit_switch(stmt),
+        Statement::Import(stmt) => visitor.visit_import(stmt),
+    }
+}
+
+pub fn walk_fun_decl<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, decl: &'a FunctionDecl) {
+    match &decl.body {
+        FunctionBody::Block(stmts) => stmts.iter().for_each(|stmt| visitor.visit_stmt(stmt)),
+        FunctionBody::Expr(expr) => visitor.visit_expr(expr),
+    }
+}
+
+pub fn walk_var_decl<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, decl: &'a VariableDecl) {
+    if let Some(initializer) = &decl.initializer {
+        visitor.visit_expr(initializer);
+    }
+}
+
+pub fn walk_destructure_decl<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, decl: &'a DestructureDecl) {
+    visitor.visit_expr(&decl.initializer);
+}
+
+/// Visits the superclass and then each method as a function declaration.
+pub fn walk_class_decl<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, decl: &'a ClassDecl) {
+    if let Some(superclass) = &decl.superclass {
+        visitor.visit_expr(superclass);
+    }
+    decl.methods.iter().for_each(|method| visitor.visit_fun_decl(method));
+}
+
+pub fn walk_print<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a PrintStatement) {
+    stmt.exprs.iter().for_each(|expr| visitor.visit_expr(expr));
+}
+
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a BlockStatement) {
+    stmt.statements.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+}
+
+pub fn walk_if<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a IfStatemnet) {
+    visitor.visit_expr(&stmt.condition);
+    visitor.visit_stmt(&stmt.if_branch);
+    if let Some(else_branch) = &stmt.else_branch {
+        visitor.visit_stmt(else_branch);
+    }
+}
+
+pub fn walk_while<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a WhileStatement) {
+    visitor.visit_expr(&stmt.condition);
+    visitor.visit_stmt(&stmt.body);
+    if let Some(increment) = &stmt.increment {
+        visitor.visit_expr(increment);
+    }
+}
+
+/// Visits the body before the condition, in the order they run.
+pub fn walk_do_while<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a WhileStatement) {
+    visitor.visit_stmt(&stmt.body);
+    visitor.visit_expr(&stmt.condition);
+}
+
+pub fn walk_return<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a ReturnStatement) {
+    if let Some(value) = &stmt.value {
+        visitor.visit_expr(value);
+    }
+}
+
+pub fn walk_try<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a TryStatement) {
+    visitor.visit_block(&stmt.try_block);
+    if let Some(catch) = &stmt.catch {
+        visitor.visit_catch(catch);
+    }
+    if let Some(finally_block) = &stmt.finally_block {
+        visitor.visit_block(finally_block);
+    }
+}
+
+/// Visits the statements of the catch block directly, since they share a scope with the error variable.
+pub fn walk_catch<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, catch: &'a CatchClause) {
+    catch.block.statements.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+}
+
+pub fn walk_using<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a UsingStatement) {
+    visitor.visit_expr(&stmt.initializer);
+    visitor.visit_block(&stmt.body);
+}
+
+pub fn walk_switch<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, stmt: &'a SwitchStatement) {
+    visitor.visit_expr(&stmt.subject);
+    for case in &stmt.cases {
+        for pattern in &case.patterns {
+            match pattern {
+                CasePattern::Value(value) | CasePattern::Guard { value, .. } => visitor.visit_expr(value),
+            }
+        }
+        case.body.iter().for_each(|stmt| visitor.visit_stmt(stmt));
+    }
+    stmt.default.iter().flatten().for_each(|stmt| visitor.visit_stmt(stmt));
+}
+
+/// Visits the operands of an expression in source order.
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(visitor: &mut V, expr: &'a Expr) {
+    match expr {
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. } => {},
+        Expr::Asign { value: expr, .. } | Expr::Unary { expr, .. } | Expr::Grouping { expr, .. } | Expr::Get { object: expr, .. } => visitor.visit_expr(expr),
+        Expr::Binary { left, right, .. } | Expr::LogicalOr { left, right, .. } | Expr::LogicalAnd { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        },
+        Expr::Call { callee, args, .. } => {
+            visitor.visit_expr(callee);
+            args.iter().for_each(|arg| visitor.visit_expr(arg));
+        },
+        Expr::Set { object, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(value);
+        },
+        Expr::List { elements, .. } | Expr::Interpolation { exprs: elements, .. } => elements.iter().for_each(|element| visitor.visit_expr(element)),
+        Expr::Map { entries, .. } => entries.iter().for_each(|(key, value)| {
+            visitor.visit_expr(key);
+            visitor.visit_expr(value);
+        }),
+        Expr::Index { object, index, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        },
+        Expr::IndexSet { object, index, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+            visitor.visit_expr(value);
+        },
+        Expr::Slice { object, start, end, .. } => {
+            visitor.visit_expr(object);
+            start.iter().chain(end).for_each(|bound| visitor.visit_expr(bound));
+        },
+        Expr::Lambda { decl } => visitor.visit_lambda(decl),
+    }
+}
+
+/// Walks a syntax tree that it may rewrite in place. Like [`Visitor`], every method visits the children of its
+/// node by default, so an implementation only overrides the nodes it rewrites.
+pub trait VisitorMut {
+    fn visit_stmt(&mut self, stmt: &mut Statement) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    /// Called for function declarations, methods and lambdas alike.
+    fn visit_fun_decl(&mut self, decl: &mut FunctionDecl) {
+        walk_fun_decl_mut(self, decl);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Statement) {
+    match stmt {
+        Statement::FunDecl(decl) => visitor.visit_fun_decl(decl),
+        Statement::VarDecl(VariableDecl { initializer, .. }) => initializer.iter_mut().for_each(|expr| visitor.visit_expr(expr)),
+        Statement::DestructureDecl(DestructureDecl { initializer, .. }) => visitor.visit_expr(initializer),
+        Statement::ClassDecl(ClassDecl { superclass, methods, .. }) => {
+            superclass.iter_mut().for_each(|expr| visitor.visit_expr(expr));
+            methods.iter_mut().for_each(|method| visitor.visit_fun_decl(method));
+        },
+        Statement::Print(PrintStatement { exprs, .. }) => exprs.iter_mut().for_each(|expr| visitor.visit_expr(expr)),
+        Statement::Expr(ExpressionStatement { expr }) | Statement::Throw(ThrowStatement { value: expr, .. }) => visitor.visit_expr(expr),
+        Statement::Block(block) => block.statements.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt)),
+        Statement::If(IfStatemnet {
+            condition,
+            if_branch,
+            else_branch,
+        }) => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(if_branch);
+            else_branch.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+        },
+        Statement::While(WhileStatement {
+            condition, body, increment, ..
+        }) => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(body);
+            increment.iter_mut().for_each(|expr| visitor.visit_expr(expr));
+        },
+        Statement::DoWhile(WhileStatement { condition, body, .. }) => {
+            visitor.visit_stmt(body);
+            visitor.visit_expr(condition);
+        },
+        Statement::Return(ReturnStatement { value, .. }) => value.iter_mut().for_each(|expr| visitor.visit_expr(expr)),
+        Statement::Break(_) | Statement::Continue(_) | Statement::Import(_) => {},
+        Statement::Try(TryStatement {
+            try_block,
+            catch,
+            finally_block,
+        }) => {
+            let catch_block = catch.iter_mut().map(|catch| &mut catch.block);
+            for block in std::iter::once(try_block).chain(catch_block).chain(finally_block.iter_mut()) {
+                block.statements.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+            }
+        },
+        Statement::Using(UsingStatement { initializer, body, .. }) => {
+            visitor.visit_expr(initializer);
+            body.statements.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+        },
+        Statement::Switch(SwitchStatement { subject, cases, default, .. }) => {
+            visitor.visit_expr(subject);
+            for SwitchCase { patterns, body } in cases {
+                for pattern in patterns {
+                    match pattern {
+                        CasePattern::Value(value) | CasePattern::Guard { value, .. } => visitor.visit_expr(value),
+                    }
+                }
+                body.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt));
+            }
+            default.iter_mut().flatten().for_each(|stmt| visitor.visit_stmt(stmt));
+        },
+    }
+}
+
+pub fn walk_fun_decl_mut<V: VisitorMut + ?Sized>(visitor: &mut V, decl: &mut FunctionDecl) {
+    match &mut decl.body {
+        FunctionBody::Block(stmts) => stmts.iter_mut().for_each(|stmt| visitor.visit_stmt(stmt)),
+        FunctionBody::Expr(expr) => visitor.visit_expr(expr),
+    }
+}
+
+/// Visits the operands of an expression in source order.
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. } => {},
+        Expr::Asign { value: expr, .. } | Expr::Unary { expr, .. } | Expr::Grouping { expr, .. } | Expr::Get { object: expr, .. } => visitor.visit_expr(expr),
+        Expr::Binary { left, right, .. } | Expr::LogicalOr { left, right, .. } | Expr::LogicalAnd { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        },
+        Expr::Call { callee, args, .. } => {
+            visitor.visit_expr(callee);
+            args.iter_mut().for_each(|arg| visitor.visit_expr(arg));
+        },
+        Expr::Set { object, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(value);
+        },
+        Expr::List { elements, .. } | Expr::Interpolation { exprs: elements, .. } => elements.iter_mut().for_each(|element| visitor.visit_expr(element)),
+        Expr::Map { entries, .. } => entries.iter_mut().for_each(|(key, value)| {
+            visitor.visit_expr(key);
+            visitor.visit_expr(value);
+        }),
+        Expr::Index { object, index, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        },
+        Expr::IndexSet { object, index, value, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+            visitor.visit_expr(value);
+        },
+        Expr::Slice { object, start, end, .. } => {
+            visitor.visit_expr(object);
+            start.iter_mut().chain(end.iter_mut()).for_each(|bound| visitor.visit_expr(bound));
+        },
+        Expr::Lambda { decl } => visitor.visit_fun_decl(decl),
+    }
+}
+
+/// Evaluates a syntax tree, producing a result for every node. Unlike [`Visitor`] nothing is walked by default:
+/// each method decides which of its children to evaluate and when, so an implementation handles every node.
+/// [`accept_stmt`] and [`accept_expr`] pick the method for a node, with boxed children passed by reference.
+pub trait ValueVisitor<'a, 't: 'a> {
+    type StmtOutput;
+    type ExprOutput;
+
+    fn visit_var_decl(&mut self, decl: &'a VariableDecl<'t>) -> Self::StmtOutput;
+    fn visit_destructure_decl(&mut self, decl: &'a DestructureDecl<'t>) -> Self::StmtOutput;
+    fn visit_fun_decl(&mut self, decl: &'a FunctionDecl<'t>) -> Self::StmtOutput;
+    fn visit_class_decl(&mut self, decl: &'a ClassDecl<'t>) -> Self::StmtOutput;
+    fn visit_print(&mut self, stmt: &'a PrintStatement<'t>) -> Self::StmtOutput;
+    fn visit_expr_stmt(&mut self, stmt: &'a ExpressionStatement<'t>) -> Self::StmtOutput;
+    fn visit_block(&mut self, stmt: &'a BlockStatement<'t>) -> Self::StmtOutput;
+    fn visit_if(&mut self, stmt: &'a IfStatemnet<'t>) -> Self::StmtOutput;
+    fn visit_while(&mut self, stmt: &'a WhileStatement<'t>) -> Self::StmtOutput;
+    fn visit_do_while(&mut self, stmt: &'a WhileStatement<'t>) -> Self::StmtOutput;
+    fn visit_return(&mut self, stmt: &'a ReturnStatement<'t>) -> Self::StmtOutput;
+    fn visit_break(&mut self, stmt: &'a LoopJump<'t>) -> Self::StmtOutput;
+    fn visit_continue(&mut self, stmt: &'a LoopJump<'t>) -> Self::StmtOutput;
+    fn visit_try(&mut self, stmt: &'a TryStatement<'t>) -> Self::StmtOutput;
+    fn visit_using(&mut self, stmt: &'a UsingStatement<'t>) -> Self::StmtOutput;
+    fn visit_switch(&mut self, stmt: &'a SwitchStatement<'t>) -> Self::StmtOutput;
+    fn visit_throw(&mut self, stmt: &'a ThrowStatement<'t>) -> Self::StmtOutput;
+    fn visit_import(&mut self, stmt: &'a ImportStatement<'t>) -> Self::StmtOutput;
+
+    fn visit_assign(&mut self, name: &Token<'t>, value: &'a Expr<'t>, height: &Cell<Option<usize>>) -> Self::ExprOutput;
+    fn visit_binary(&mut self, left: &'a Expr<'t>, operator: &Token<'t>, right: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_unary(&mut self, operator: &Token<'t>, expr: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_grouping(&mut self, expr: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_literal(&mut self, value: &'a Literal<'t>) -> Self::ExprOutput;
+    fn visit_variable(&mut self, name: &Token<'t>, height: &Cell<Option<usize>>, this_height: &Cell<Option<usize>>) -> Self::ExprOutput;
+    fn visit_logical_or(&mut self, left: &'a Expr<'t>, right: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_logical_and(&mut self, left: &'a Expr<'t>, right: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_call(&mut self, callee: &'a Expr<'t>, paren: &Token<'t>, args: &'a [Expr<'t>]) -> Self::ExprOutput;
+    fn visit_get(&mut self, object: &'a Expr<'t>, name: &Token<'t>, optional: bool) -> Self::ExprOutput;
+    fn visit_set(&mut self, object: &'a Expr<'t>, name: &Token<'t>, value: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_this(&mut self, keyword: &Token<'t>, height: &Cell<Option<usize>>) -> Self::ExprOutput;
+    fn visit_super(&mut self, keyword: &Token<'t>, method: &Token<'t>, height: &Cell<Option<usize>>) -> Self::ExprOutput;
+    fn visit_list(&mut self, elements: &'a [Expr<'t>]) -> Self::ExprOutput;
+    fn visit_map(&mut self, entries: &'a [(Expr<'t>, Expr<'t>)]) -> Self::ExprOutput;
+    fn visit_interpolation(&mut self, segments: &[Cow<'t, str>], exprs: &'a [Expr<'t>]) -> Self::ExprOutput;
+    fn visit_index(&mut self, object: &'a Expr<'t>, bracket: &Token<'t>, index: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_index_set(&mut self, object: &'a Expr<'t>, bracket: &Token<'t>, index: &'a Expr<'t>, value: &'a Expr<'t>) -> Self::ExprOutput;
+    fn visit_slice(&mut self, object: &'a Expr<'t>, bracket: &Token<'t>, start: Option<&'a Expr<'t>>, end: Option<&'a Expr<'t>>) -> Self::ExprOutput;
+    fn visit_lambda(&mut self, decl: &'a FunctionDecl<'t>) -> Self::ExprOutput;
+}
+
+pub fn accept_stmt<'a, 't, V: ValueVisitor<'a, 't> + ?Sized>(visitor: &mut V, stmt: &'a Statement<'t>) -> V::StmtOutput {
+    match stmt {
+        Statement::VarDecl(decl) => visitor.visit_var_decl(decl),
+        Statement::DestructureDecl(decl) => visitor.visit_destructure_decl(decl),
+        Statement::FunDecl(decl) => visitor.visit_fun_decl(decl),
+        Statement::ClassDecl(decl) => visitor.visit_class_decl(decl),
+        Statement::Print(stmt) => visitor.visit_print(stmt),
+        Statement::Expr(stmt) => visitor.visit_expr_stmt(stmt),
+        Statement::Block(stmt) => visitor.visit_block(stmt),
+        Statement::If(stmt) => visitor.visit_if(stmt),
+        Statement::While(stmt) => visitor.visit_while(stmt),
+        Statement::DoWhile(stmt) => visitor.visit_do_while(stmt),
+        Statement::Return(stmt) => visitor.visit_return(stmt),
+        Statement::Break(stmt) => visitor.visit_break(stmt),
+        Statement::Continue(stmt) => visitor.visit_continue(stmt),
+        Statement::Try(stmt) => visitor.visit_try(stmt),
+        Statement::Using(stmt) => visitor.visit_using(stmt),
+        Statement::Switch(stmt) => visitor.visit_switch(stmt),
+        Statement::Throw(stmt) => visitor.visit_throw(stmt),
+        Statement::Import(stmt) => visitor.visit_import(stmt),
+    }
+}
+
+pub fn accept_expr<'a, 't, V: ValueVisitor<'a, 't> + ?Sized>(visitor: &mut V, expr: &'a Expr<'t>) -> V::ExprOutput {
+    match expr {
+        Expr::Asign { name, value, height } => visitor.visit_assign(name, value, height),
+        Expr::Binary { left, operator, right } => visitor.visit_binary(left, operator, right),
+        Expr::Unary { operator, expr } => visitor.visit_unary(operator, expr),
+        Expr::Grouping { expr, .. } => visitor.visit_grouping(expr),
+        Expr::Literal { value, .. } => visitor.visit_literal(value),
+        Expr::Variable { name, height, this_height } => visitor.visit_variable(name, height, this_height),
+        Expr::LogicalOr { left, right, .. } => visitor.visit_logical_or(left, right),
+        Expr::LogicalAnd { left, right, .. } => visitor.visit_logical_and(left, right),
+        Expr::Call { callee, paren, args } => visitor.visit_call(callee, paren, args),
+        Expr::Get { object, name, optional } => visitor.visit_get(object, name, *optional),
+        Expr::Set { object, name, value } => visitor.visit_set(object, name, value),
+        Expr::This { keyword, height } => visitor.visit_this(keyword, height),
+        Expr::Super { keyword, method, height } => visitor.visit_super(keyword, method, height),
+        Expr::List { elements, .. } => visitor.visit_list(elements),
+        Expr::Map { entries, .. } => visitor.visit_map(entries),
+        Expr::Interpolation { segments, exprs, .. } => visitor.visit_interpolation(segments, exprs),
+        Expr::Index { object, bracket, index } => visitor.visit_index(object, bracket, index),
+        Expr::IndexSet { object, bracket, index, value } => visitor.visit_index_set(object, bracket, index, value),
+        Expr::Slice { object, bracket, start, end } => visitor.visit_slice(object, bracket, start.as_deref(), end.as_deref()),
+        Expr::Lambda { decl } => visitor.visit_lambda(decl),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_switch_to_scientific_notation_at_the_thresholds() {
+        let cases = [
+            (9999999999999998.0, "9999999999999998"),
+            (1e16, "1e16"),
+            (-2.5e20, "-2.5e20"),
+            (0.0001, "0.0001"),
+            (0.00009, "9e-5"),
+            (-1.5e-7, "-1.5e-7"),
+            (0.0, "0"),
+            (f64::INFINITY, "inf"),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(format_number(n, None), expected);
+        }
+    }
+
+    #[test]
+    fn numbers_print_like_parsed_literals_around_the_thresholds() {
+        for n in [1e15, 1e16, 1.5e17, 0.0001, 0.000099] {
+            let literal = format!("{n:?}");
+            assert_eq!(literal.contains('e'), format_number(n, None).contains('e'), "{literal}");
+        }
+    }
+
+    #[test]
+    fn rounding_applies_before_choosing_the_notation() {
+        assert_eq!(format_number(123456789e12, Some(3)), "1.23e20");
+        assert_eq!(format_number(9999999999999999.0, Some(3)), "1e16");
+        assert_eq!(format_number(0.000123456, Some(2)), "0.00012");
+    }
+}
@@ -12,10 +12,21 @@ impl<'a> Token<'a> {
             token_type,
             lexeme,
             literal,
-            pos: TokenPosition { line, offset },
+            pos: TokenPosition {
+                line,
+                offset,
+                end_line: line,
+                end_offset: offset,
+            },
         }
     }
 
+    pub fn ending_at(mut self, end_line: u64, end_offset: u64) -> Self {
+        self.pos.end_line = end_line;
+        self.pos.end_offset = end_offset;
+        self
+    }
+
     pub fn symbol(token_type: TokenType, lexeme: &'a str, line: u64, offset: u64) -> Self {
         Self::new(token_type, lexeme.into(), TokenLiteral::NoValue, line, offset)
     }
@@ -24,18 +35,34 @@ impl<'a> Token<'a> {
         Self::new(identifier_type(value), value, TokenLiteral::NoValue, line, offset)
     }
 
-    pub fn string(value: &'a str, line: u64, offset: u64) -> Self {
-        let value = &value[1..value.len() - 1];
-        Self::new(TokenType::String, value, TokenLiteral::String(value), line, offset)
+    pub fn string(lexeme: &'a str, line: u64, offset: u64) -> Self {
+        let value = &lexeme[1..lexeme.len() - 1];
+        Self::new(TokenType::String, lexeme, TokenLiteral::String(value), line, offset)
+    }
+
+    /// A piece of a string that ends where an interpolated `${` expression begins.
+    pub fn interpolation(lexeme: &'a str, line: u64, offset: u64) -> Self {
+        let value = &lexeme[1..lexeme.len() - 2];
+        Self::new(TokenType::Interpolation, lexeme, TokenLiteral::String(value), line, offset)
+    }
+
+    pub fn raw_string(lexeme: &'a str, line: u64, offset: u64) -> Self {
+        let value = &lexeme[2..lexeme.len() - 1];
+        Self::new(TokenType::String, lexeme, TokenLiteral::String(value), line, offset)
     }
 
     pub fn number(value: &'a str, line: u64, offset: u64) -> Self {
-        let n = value.parse().unwrap();
+        // `_` separates digits for readability and has no value of its own
+        let n = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+            Some(hex) => hex.chars().filter_map(|digit| digit.to_digit(16)).fold(0.0, |n, digit| n * 16.0 + digit as f64),
+            None => value.replace('_', "").parse().unwrap(),
+        };
         Self::new(TokenType::Number, value, TokenLiteral::Number(n), line, offset)
     }
 
-    pub fn eof(line: u64) -> Self {
-        Self::new(TokenType::Eof, "", TokenLiteral::NoValue, line, 0)
+    /// The end of the source, positioned just past its last byte.
+    pub fn eof(line: u64, offset: u64) -> Self {
+        Self::new(TokenType::Eof, "", TokenLiteral::NoValue, line, offset)
     }
 }
 
@@ -43,6 +70,8 @@ impl<'a> Token<'a> {
 pub struct TokenPosition {
     pub line: u64,
     pub offset: u64,
+    pub end_line: u64,
+    pub end_offset: u64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -51,14 +80,22 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
+    Colon,
     Plus,
     Minus,
     Dot,
+    /// `?.`, reading a property unless the object is `nil`.
+    QuestionDot,
     SemiColon,
     Star,
+    StarStar,
     Comma,
     Not,
     Asign,
+    /// `=>`, separating a function's parameters from an expression body.
+    Arrow,
     Equal,
     NotEqual,
     Less,
@@ -66,23 +103,40 @@ pub enum TokenType {
     Greater,
     GreaterEq,
     String,
+    Interpolation,
     Identifier,
     Number,
     Div,
+    /// Floored division, spelled `\` since `//` starts a comment: `7 \ 2` is `3` and `-7 \ 2` is `-4`.
+    FloorDiv,
     And,
+    As,
+    Break,
+    Case,
+    Catch,
     Class,
+    Continue,
+    Default,
+    Do,
     Else,
     False,
+    Finally,
     For,
     Fun,
     If,
+    Import,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
+    Using,
     Var,
     While,
     Eof,
@@ -99,19 +153,35 @@ pub fn identifier_type(s: &str) -> TokenType {
     use TokenType::*;
     match s {
         "and" => And,
+        "as" => As,
+        "break" => Break,
+        "case" => Case,
+        "catch" => Catch,
         "class" => Class,
+        "continue" => Continue,
+        "default" => Default,
+        "do" => Do,
         "else" => Else,
         "false" => False,
+        "finally" => Finally,
         "for" => For,
         "fun" => Fun,
         "if" => If,
+        "import" => Import,
+        "in" => In,
+        // `let` declares variables exactly like `var`
+        "let" => Var,
         "nil" => Nil,
         "or" => Or,
         "print" => Print,
         "return" => Return,
         "super" => Super,
+        "switch" => Switch,
         "this" => This,
+        "throw" => Throw,
         "true" => True,
+        "try" => Try,
+        "using" => Using,
         "var" => Var,
         "while" => While,
         _ => Identifier,
@@ -1,6 +1,6 @@
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 
 #[cfg(feature = "integration-tests")]
 #[test]
@@ -22,38 +22,44 @@ fn test_lox_files() {
         let path = entry.path();
 
         if path.extension().and_then(|s| s.to_str()) == Some("lox") {
-            let test_name = path.file_stem().unwrap().to_str().unwrap();
-            let expected_path = path.with_extension("expected");
-
-            if !expected_path.exists() {
-                continue;
-            }
-
-            println!("Running test: {}", test_name);
-
-            let expected_output = fs::read_to_string(&expected_path)
-                .expect("Could not read expected file")
-                .replace("\r\n", "\n");
-
-            // Run the binary directly from target/debug
-            // The name comes from Cargo.toml: codecrafters-interpreter
-            let output = Command::new("./target/debug/codecrafters-interpreter")
-                .args(["run", path.to_str().unwrap()])
-                .output()
-                .expect("Failed to execute command");
-
-            let actual_output = String::from_utf8_lossy(&output.stdout)
-                .replace("\r\n", "\n")
-                .trim_end()
-                .to_string();
-
-            let expected_output_trimmed = expected_output.trim_end().to_string();
-
-            if actual_output != expected_output_trimmed {
-                failed_tests.push(format!(
-                    "Test '{}' failed.\nExpected:\n---\n{}\n---\nActual:\n---\n{}\n---",
-                    test_name, expected_output_trimmed, actual_output
-                ));
+            // `.expected` holds the output of `run`, `.tokens` the output of `tokenize`, `.formatted` the output of `fmt`
+            // `.resolved` the output of `run --dump-resolved`, `.folded` the output of `fmt --fold-constants`
+            // `.last` the output of `run --print-last`, `.args` the output of `run` with script arguments,
+            // `.limited` the output of `run --max-steps=1000`, `.implicit` the output of `run --implicit-methods`,
+            // `.strict` the output of `run --strict` with every warning enabled,
+            // `.relaxed` the output of `run --optional-semicolons`, `.scoped` the output of `run --max-scope-vars=4`
+            // `.metrics` the output of `metrics`, `.shadowing` the output of `run --warn-shadowing`, `.no_effect`
+            // the output of `run --warn-no-effect`, `.traced` the output of `run --trace`, `.checked` the output of `check`
+            // `.evaluated` the output of `evaluate`, `.rounded` the output of `run --precision=3` and `.scientific`
+            // the output of `run --scientific-from=1e6 --scientific-below=0.01`.
+            // A `.stderr` or `.status` file after any of these extensions,
+            // like `strict.strict.stderr`, holds the error output or the exit code of the same command.
+            let commands: [(&[&str], &str); 20] = [
+                (&["run"], "expected"),
+                (&["tokenize"], "tokens"),
+                (&["fmt"], "formatted"),
+                (&["run", "--dump-resolved"], "resolved"),
+                (&["fmt", "--fold-constants"], "folded"),
+                (&["run", "--print-last"], "last"),
+                (&["run", "--", "first", "--second"], "args"),
+                (&["run", "--max-steps=1000"], "limited"),
+                (&["run", "--implicit-methods"], "implicit"),
+                (&["run", "--strict", "--warn-shadowing", "--warn-no-effect", "--warn-super-init"], "strict"),
+                (&["run", "--optional-semicolons"], "relaxed"),
+                (&["run", "--max-scope-vars=4"], "scoped"),
+                (&["metrics"], "metrics"),
+                (&["run", "--warn-shadowing"], "shadowing"),
+                (&["run", "--warn-no-effect"], "no_effect"),
+                (&["run", "--trace"], "traced"),
+                (&["check"], "checked"),
+                (&["evaluate"], "evaluated"),
+                (&["run", "--precision=3"], "rounded"),
+                (&["run", "--scientific-from=1e6", "--scientific-below=0.01"], "scientific"),
+            ];
+            for (command, extension) in commands {
+                if let Some(failure) = check_output(&path, command, extension) {
+                    failed_tests.push(failure);
+                }
             }
         }
     }
@@ -66,3 +72,231 @@ fn test_lox_files() {
         );
     }
 }
+
+#[cfg(feature = "integration-tests")]
+fn check_output(path: &Path, command: &[&str], extension: &str) -> Option<String> {
+    let test_name = path.file_stem().unwrap().to_str().unwrap();
+    let expected_path = path.with_extension(extension);
+
+    if !expected_path.exists() {
+        return None;
+    }
+
+    let (subcommand, flags) = command.split_first().expect("Missing subcommand");
+    let command = command.join(" ");
+    println!("Running test: {} ({})", test_name, command);
+
+    let expected_output = fs::read_to_string(&expected_path)
+        .expect("Could not read expected file")
+        .replace("\r\n", "\n");
+
+    // Run the binary directly from target/debug
+    // The name comes from Cargo.toml: codecrafters-interpreter
+    let output = Command::new("./target/debug/codecrafters-interpreter")
+        .arg(subcommand)
+        .arg(path)
+        .args(flags)
+        .output()
+        .expect("Failed to execute command");
+
+    let actual_output = String::from_utf8_lossy(&output.stdout)
+        .replace("\r\n", "\n")
+        .trim_end()
+        .to_string();
+
+    let expected_output_trimmed = expected_output.trim_end().to_string();
+
+    if actual_output != expected_output_trimmed {
+        return Some(format!(
+            "Test '{}' ({}) failed.\nExpected:\n---\n{}\n---\nActual:\n---\n{}\n---",
+            test_name, command, expected_output_trimmed, actual_output
+        ));
+    }
+
+    let stderr_path = path.with_extension(format!("{extension}.stderr"));
+    if stderr_path.exists() {
+        let expected_errors = fs::read_to_string(&stderr_path).expect("Could not read stderr file").replace("\r\n", "\n");
+        let expected_errors = expected_errors.trim_end();
+        let actual_errors = String::from_utf8_lossy(&output.stderr).replace("\r\n", "\n");
+        let actual_errors = actual_errors.trim_end();
+        if actual_errors != expected_errors {
+            return Some(format!(
+                "Test '{}' ({}) failed on stderr.\nExpected:\n---\n{}\n---\nActual:\n---\n{}\n---",
+                test_name, command, expected_errors, actual_errors
+            ));
+        }
+    }
+
+    let status_path = path.with_extension(format!("{extension}.status"));
+    if status_path.exists() {
+        let expected_status: i32 = fs::read_to_string(&status_path)
+            .expect("Could not read status file")
+            .trim()
+            .parse()
+            .expect("Status file must hold an exit code");
+        if output.status.code() != Some(expected_status) {
+            return Some(format!(
+                "Test '{}' ({}) failed: expected exit code {}, got {:?}",
+                test_name,
+                command,
+                expected_status,
+                output.status.code()
+            ));
+        }
+    }
+    None
+}
+
+/// Mistakes on the command line stop the interpreter with a usage error before it reads any script.
+#[cfg(feature = "integration-tests")]
+#[test]
+fn command_line_mistakes_exit_with_a_usage_error() {
+    let build_status = Command::new("cargo").arg("build").status().expect("Failed to build project");
+    assert!(build_status.success(), "Build failed");
+
+    for args in [
+        &["run", "tests-files/strict_clean.lox", "--strcit"][..],
+        &["check", "tests-files/strict_clean.lox", "--max-errors=0"],
+        &["run", "tests-files/strict_clean.lox", "--precision=x"],
+        &["run", "tests-files/strict_clean.lox", "--precision=0"],
+        &["run", "tests-files/strict_clean.lox", "--scientific-from=0"],
+        &["run", "tests-files/strict_clean.lox", "--scientific-below=x"],
+        &["compile", "tests-files/strict_clean.lox"],
+        &["run"],
+    ] {
+        let output = Command::new("./target/debug/codecrafters-interpreter")
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+        assert_eq!(output.status.code(), Some(64), "{args:?}");
+        assert!(output.stdout.is_empty(), "{args:?}");
+    }
+}
+
+/// Runs the interpreter with `input` piped to its standard input.
+#[cfg(feature = "integration-tests")]
+fn run_with_stdin(args: &[&str], input: &str) -> Output {
+    use std::io::Write;
+
+    let build_status = Command::new("cargo").arg("build").status().expect("Failed to build project");
+    assert!(build_status.success(), "Build failed");
+
+    let mut child = Command::new("./target/debug/codecrafters-interpreter")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to execute command");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).expect("Failed to write to stdin");
+    child.wait_with_output().expect("Failed to wait for command")
+}
+
+/// A `-` filename reads the script from standard input, for every command.
+#[cfg(feature = "integration-tests")]
+#[test]
+fn dash_reads_the_script_from_stdin() {
+    let script = "var x = 1 + 2;\nprint x;\n";
+    for (command, expected) in [
+        ("run", "3"),
+        ("evaluate", "3"),
+        ("tokenize", "VAR var null\nIDENTIFIER x null\nEQUAL = null\nNUMBER 1 1.0\nPLUS + null\nNUMBER 2 2.0\nSEMICOLON ; null\nPRINT print null\nIDENTIFIER x null\nSEMICOLON ; null\nEOF  null"),
+        ("fmt", "var x = 1 + 2;\nprint x;"),
+        ("check", ""),
+    ] {
+        let output = run_with_stdin(&[command, "-"], script);
+        assert!(output.status.success(), "{command}: {output:?}");
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), expected, "{command}");
+    }
+
+    // Errors point at lines of the piped script
+    let output = run_with_stdin(&["run", "-"], "print 1;\nprint -\"a\";\n");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[line 2]"));
+}
+
+/// Each REPL line that fails is reported and abandoned, and the lines after it run in the global scope as before.
+#[cfg(feature = "integration-tests")]
+#[test]
+fn repl_recovers_from_errors_between_lines() {
+    let lines = [
+        "var x = 1;",
+        "print (x;",
+        "return x;",
+        "{ var y = 2; print y + nil; }",
+        "fun f() { var z = 3; return z - \"a\"; }",
+        "f();",
+        "var w = x + 1;",
+        "{ var y = w * 10; y }",
+        "y",
+    ];
+    let output = run_with_stdin(&["repl"], &(lines.join("\n") + "\n"));
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "> > > > > > > > 20\n> > ");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        [
+            "[line 1] Error at ';': Expect ')' after expression.",
+            "[line 1] Error at 'return': Can't return from top-level code.",
+            "Operands must be numbers",
+            "[line 1]",
+            "Operands must be numbers",
+            "[line 1]",
+            "in f(), called from line 1",
+            "Undefined variable 'y'.",
+            "[line 1]\n",
+        ]
+        .join("\n")
+    );
+}
+
+#[cfg(feature = "integration-tests")]
+#[test]
+fn repl_warns_when_a_declaration_replaces_a_global() {
+    let lines = ["var x = 1;", "fun x() {}", "{ var x = 2; }", "var y; class y {}"];
+    let output = run_with_stdin(&["repl"], &(lines.join("\n") + "\n"));
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        [
+            "[line 1] Warning at 'x': Replaces the global of the same name.",
+            "[line 1] Warning at 'y': Replaces the global of the same name.\n",
+        ]
+        .join("\n")
+    );
+}
+
+/// `--time` reports how long each stage took on stderr and leaves the script's output alone.
+#[cfg(feature = "integration-tests")]
+#[test]
+fn time_reports_stage_durations_on_stderr() {
+    let output = run_with_stdin(&["run", "tests-files/strict_clean.lox", "--time"], "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stages: Vec<_> = stderr.lines().map(|line| line.split_once(':').expect("stage name").0).collect();
+    assert_eq!(stages, ["parse", "resolve", "execute"]);
+    assert!(stderr.lines().all(|line| line.ends_with('s')), "{stderr}");
+}
+
+#[cfg(feature = "integration-tests")]
+#[test]
+fn time_reports_the_stages_that_finished_when_the_runtime_fails() {
+    let output = run_with_stdin(&["run", "tests-files/not_indexable.lox", "--time"], "");
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stages: Vec<_> = stderr.lines().skip(2).map(|line| line.split_once(':').expect("stage name").0).collect();
+    assert_eq!(stages, ["parse", "resolve"]);
+}
+
+#[cfg(feature = "integration-tests")]
+#[test]
+fn time_reports_the_stages_that_ran_before_dumping_resolutions() {
+    let output = run_with_stdin(&["run", "tests-files/strict_clean.lox", "--dump-resolved", "--time"], "");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stages: Vec<_> = stderr.lines().map(|line| line.split_once(':').expect("stage name").0).collect();
+    assert_eq!(stages, ["parse", "resolve"]);
+}